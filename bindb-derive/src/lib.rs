@@ -0,0 +1,443 @@
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, format_ident};
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Error, Ident, Lit, Member, Meta, NestedMeta, Path, Result, Type};
+
+/// Derives `bindb::Table` for a struct, along with one `bindb::Index` per `#[bindb(index)]` field.
+///
+/// Supported attributes:
+/// * `#[bindb(name = "...")]` on the struct - name of the table in the database. Defaults to the
+///   struct name, set it explicitly to keep the data when renaming the struct.
+/// * `#[bindb(version = N)]` on the struct - schema version of the table, defaults to 0.
+/// * `#[bindb(migrations = "path::to::fn")]` on the struct - function returning `bindb::Migrations`
+///   for this table.
+/// * `#[bindb(codec = "bindb::Bincode")]` on the struct - format of stored values, defaults to `bindb::Json`.
+/// * `#[bindb(key)]` on exactly one field - primary key of the table.
+/// * `#[bindb(index)]` or `#[bindb(index = "Name")]` on a field - secondary index. Index type is
+///   named `<Struct><Field>Idx` unless specified.
+/// * `#[bindb(unique)]` next to `index` - index is keyed by the field alone. Non-unique indices
+///   include the primary key in their key.
+/// * `#[bindb(references(table = "Repo", field = "repo_id", on_delete = "cascade", owned))]` on a field -
+///   the field, or its subfield at path `field`, holds the primary key of `table`. `on_delete` is either
+///   `cascade` or `restrict` (default), `owned` deletes the referenced row once no owned reference points to it.
+///   Reference type is named `<Struct><Table>Ref` unless specified by `name = "..."`, which is required
+///   when a struct references the same table more than once.
+/// * `#[bindb(text)]` or `#[bindb(text = "Name")]` on string fields - full-text index over all fields with the
+///   same index name. Index type is named `<Struct>Text` unless specified.
+/// * `#[bindb(expires)]` on a `u64` or `Option<u64>` field - expiry time of the row in seconds since unix epoch.
+///   Rows are deleted by `Database::sweep` once expired, through an index named `<Struct>Expiry`.
+#[proc_macro_derive(Table, attributes(bindb))]
+pub fn derive_table(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+struct Field {
+    member: Member,
+    name: String,
+    ty: Type,
+}
+
+struct IndexDef {
+    ident: Ident,
+    name: String,
+    field: Field,
+    unique: bool,
+}
+
+struct TextDef {
+    ident: Ident,
+    name: String,
+    members: Vec<Member>,
+}
+
+struct ReferenceDef {
+    ident: Ident,
+    name: String,
+    member: Member,
+    path: Vec<Member>,
+    parent: Path,
+    on_delete: Ident,
+    owned: bool,
+}
+
+fn parse_reference(table: &Ident, table_name: &str, field: &Field, args: impl IntoIterator<Item=NestedMeta>) -> Result<ReferenceDef> {
+    let mut parent = None;
+    let mut path = vec![];
+    let mut on_delete = format_ident!("Restrict");
+    let mut owned = false;
+    let mut name = None;
+
+    for arg in args {
+        match arg {
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("name") => name = Some(lit_str(&nv.lit)?),
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("table") => {
+                parent = Some(syn::parse_str::<Path>(&lit_str(&nv.lit)?)?)
+            }
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("field") => {
+                path = lit_str(&nv.lit)?.split('.').map(syn::parse_str::<Member>).collect::<Result<_>>()?
+            }
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("on_delete") => {
+                on_delete = match lit_str(&nv.lit)?.as_str() {
+                    "cascade" => format_ident!("Cascade"),
+                    "restrict" => format_ident!("Restrict"),
+                    _ => return Err(Error::new_spanned(nv.lit, "expected \"cascade\" or \"restrict\"")),
+                }
+            }
+            NestedMeta::Meta(Meta::Path(p)) if p.is_ident("owned") => owned = true,
+            other => return Err(Error::new_spanned(other, "unknown bindb reference attribute")),
+        }
+    }
+
+    let parent = parent.ok_or_else(|| Error::new_spanned(table, "reference is missing `table`"))?;
+    let parent_name = parent.segments.last().unwrap().ident.to_string();
+    let path_name = std::iter::once(field.name.clone())
+        .chain(path.iter().map(|m| quote!(#m).to_string()))
+        .collect::<Vec<_>>()
+        .join("_");
+
+    // Like index names, default names are derived from the table name
+    let (ident, name) = match name {
+        Some(name) => (format_ident!("{}", name), format!("{}_{}", name, path_name)),
+        None => (
+            format_ident!("{}{}Ref", table, parent_name),
+            format!("{}{}Ref_{}", table_name, parent_name, path_name),
+        ),
+    };
+
+    Ok(ReferenceDef {
+        ident,
+        name,
+        member: field.member.clone(),
+        path,
+        parent,
+        on_delete,
+        owned,
+    })
+}
+
+fn bindb_meta(attrs: &[Attribute]) -> Result<Vec<NestedMeta>> {
+    let mut out = vec![];
+    for attr in attrs.iter().filter(|a| a.path.is_ident("bindb")) {
+        match attr.parse_meta()? {
+            Meta::List(list) => out.extend(list.nested),
+            other => return Err(Error::new_spanned(other, "expected #[bindb(...)]")),
+        }
+    }
+    Ok(out)
+}
+
+fn lit_str(lit: &Lit) -> Result<String> {
+    match lit {
+        Lit::Str(s) => Ok(s.value()),
+        other => Err(Error::new_spanned(other, "expected string literal")),
+    }
+}
+
+fn camel_case(name: &str) -> String {
+    name.split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(c) => c.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+fn expand(input: DeriveInput) -> Result<TokenStream2> {
+    let ident = &input.ident;
+    let vis = &input.vis;
+
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(&input.generics, "Table can not be derived for generic types"));
+    }
+
+    let mut name = ident.to_string();
+    let mut version = None;
+    let mut migrations = None;
+    let mut codec = None;
+    for meta in bindb_meta(&input.attrs)? {
+        match meta {
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("name") => name = lit_str(&nv.lit)?,
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("version") => match &nv.lit {
+                Lit::Int(v) => version = Some(v.base10_parse::<u32>()?),
+                other => return Err(Error::new_spanned(other, "expected integer literal")),
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("migrations") => {
+                migrations = Some(syn::parse_str::<Path>(&lit_str(&nv.lit)?)?)
+            }
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("codec") => {
+                codec = Some(syn::parse_str::<Type>(&lit_str(&nv.lit)?)?)
+            }
+            other => return Err(Error::new_spanned(other, "unknown bindb table attribute")),
+        }
+    }
+
+    let fields = match &input.data {
+        Data::Struct(s) => &s.fields,
+        _ => return Err(Error::new_spanned(ident, "Table can only be derived for structs")),
+    };
+
+    let mut key: Option<Field> = None;
+    let mut indices = vec![];
+    let mut references = vec![];
+    let mut texts: Vec<TextDef> = vec![];
+    let mut expires: Option<Field> = None;
+
+    for (i, f) in fields.iter().enumerate() {
+        let field = match &f.ident {
+            Some(id) => Field { member: Member::Named(id.clone()), name: id.to_string(), ty: f.ty.clone() },
+            None => Field { member: Member::Unnamed(i.into()), name: i.to_string(), ty: f.ty.clone() },
+        };
+
+        let mut is_key = false;
+        let mut index: Option<Option<String>> = None;
+        let mut unique = false;
+        let mut field_refs = vec![];
+        let mut text: Option<Option<String>> = None;
+        let mut is_expiry = false;
+
+        for meta in bindb_meta(&f.attrs)? {
+            match meta {
+                NestedMeta::Meta(Meta::Path(p)) if p.is_ident("key") => is_key = true,
+                NestedMeta::Meta(Meta::Path(p)) if p.is_ident("index") => index = Some(None),
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("index") => {
+                    index = Some(Some(lit_str(&nv.lit)?))
+                }
+                NestedMeta::Meta(Meta::Path(p)) if p.is_ident("unique") => unique = true,
+                NestedMeta::Meta(Meta::Path(p)) if p.is_ident("text") => text = Some(None),
+                NestedMeta::Meta(Meta::Path(p)) if p.is_ident("expires") => is_expiry = true,
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("text") => {
+                    text = Some(Some(lit_str(&nv.lit)?))
+                }
+                NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("references") => field_refs.push(list.nested),
+                other => return Err(Error::new_spanned(other, "unknown bindb field attribute")),
+            }
+        }
+
+        if unique && index.is_none() {
+            return Err(Error::new_spanned(f, "`unique` requires `index`"));
+        }
+
+        if let Some(idx) = index {
+            // Default names are derived from the table name, so `#[bindb(name)]` pins indices as well
+            let (idx_ident, idx_name) = match idx {
+                Some(idx) => (format_ident!("{}", idx), idx),
+                None => (
+                    format_ident!("{}{}Idx", ident, camel_case(&field.name)),
+                    format!("{}{}Idx", name, camel_case(&field.name))
+                ),
+            };
+            indices.push(IndexDef {
+                ident: idx_ident,
+                name: idx_name,
+                field: Field { member: field.member.clone(), name: field.name.clone(), ty: field.ty.clone() },
+                unique,
+            });
+        }
+
+        if let Some(text) = text {
+            let (text_ident, text_name) = match text {
+                Some(text) => (format_ident!("{}", text), text),
+                None => (format_ident!("{}Text", ident), format!("{}Text", name)),
+            };
+            match texts.iter_mut().find(|t| t.ident == text_ident) {
+                Some(def) => def.members.push(field.member.clone()),
+                None => texts.push(TextDef { ident: text_ident, name: text_name, members: vec![field.member.clone()] }),
+            }
+        }
+
+        for args in field_refs {
+            let reference = parse_reference(ident, &name, &field, args)?;
+            if references.iter().any(|r: &ReferenceDef| r.ident == reference.ident) {
+                return Err(Error::new_spanned(f, format!(
+                    "{} is already defined by another reference, name this one with `name = \"...\"`",
+                    reference.ident
+                )));
+            }
+            references.push(reference);
+        }
+
+        if is_expiry {
+            if expires.is_some() {
+                return Err(Error::new_spanned(f, "only one field can be marked as #[bindb(expires)]"));
+            }
+            expires = Some(Field { member: field.member.clone(), name: field.name.clone(), ty: field.ty.clone() });
+        }
+
+        if is_key {
+            if key.is_some() {
+                return Err(Error::new_spanned(f, "only one field can be marked as #[bindb(key)]"));
+            }
+            key = Some(field);
+        }
+    }
+
+    let key = key.ok_or_else(|| Error::new_spanned(ident, "missing #[bindb(key)] field"))?;
+    let key_member = &key.member;
+    let key_ty = &key.ty;
+
+    let ref_idents = references.iter().map(|r| &r.ident).collect::<Vec<_>>();
+    let text_idents = texts.iter().map(|t| &t.ident).collect::<Vec<_>>();
+    // References are maintained as indices
+    let idx_idents = indices.iter().map(|i| &i.ident).chain(ref_idents.iter().cloned()).collect::<Vec<_>>();
+
+    let expiry_ident = expires.as_ref().map(|_| format_ident!("{}Expiry", ident));
+    let expiry_impl = expires.as_ref().map(|field| {
+        let member = &field.member;
+        let expiry_name = format!("{}Expiry_{}_{}", name, field.name, key.name);
+        quote! {
+            #vis struct #expiry_ident {}
+
+            impl ::bindb::Index for #expiry_ident {
+                type Table = #ident;
+                const NAME: &'static str = #expiry_name;
+                type Key = (::bindb::Timestamp, #key_ty);
+                type KeyRef<'a> = (::bindb::Timestamp, &'a #key_ty);
+
+                fn get<'a>(t: &'a Self::Table) -> Self::KeyRef<'a> {
+                    (::bindb::Timestamp(::bindb::ExpiresAt::expires_at(&t.#member)), &t.#key_member)
+                }
+            }
+        }
+    });
+    let expiry_idents = expiry_ident.iter().collect::<Vec<_>>();
+
+    let text_impls = texts.iter().map(|t| {
+        let text_ident = &t.ident;
+        let text_name = &t.name;
+        let members = &t.members;
+
+        quote! {
+            #vis struct #text_ident {}
+
+            impl ::bindb::TextIndex for #text_ident {
+                type Table = #ident;
+                const NAME: &'static str = #text_name;
+
+                fn text<'a>(t: &'a Self::Table, out: &mut Vec<&'a str>) {
+                    #( ::bindb::TextSource::collect_text(&t.#members, out); )*
+                }
+            }
+        }
+    });
+
+    let reference_impls = references.iter().map(|r| {
+        let ref_ident = &r.ident;
+        let ref_name = &r.name;
+        let member = &r.member;
+        let path = &r.path;
+        let parent = &r.parent;
+        let on_delete = &r.on_delete;
+        let owned = r.owned;
+
+        quote! {
+            #vis struct #ref_ident {}
+
+            impl ::bindb::Index for #ref_ident {
+                type Table = #ident;
+                const NAME: &'static str = #ref_name;
+                type Key = (<#parent as ::bindb::Table>::Key, #key_ty);
+                type KeyRef<'a> = (&'a <#parent as ::bindb::Table>::Key, &'a #key_ty);
+
+                fn get<'a>(t: &'a Self::Table) -> Self::KeyRef<'a> {
+                    (&t.#member #(.#path)*, &t.#key_member)
+                }
+            }
+
+            impl ::bindb::Reference for #ref_ident {
+                type Parent = #parent;
+                const ON_DELETE: ::bindb::OnDelete = ::bindb::OnDelete::#on_delete;
+                const OWNED: bool = #owned;
+
+                fn parent_key(t: &Self::Table) -> &<#parent as ::bindb::Table>::Key {
+                    &t.#member #(.#path)*
+                }
+            }
+        }
+    });
+
+    let index_impls = indices.iter().map(|idx| {
+        let idx_ident = &idx.ident;
+        let member = &idx.field.member;
+        let ty = &idx.field.ty;
+
+        // Keep the naming scheme of `index!`, so both produce the same database names
+        if idx.unique {
+            let idx_name = format!("{}_{}", idx.name, idx.field.name);
+            quote! {
+                #vis struct #idx_ident {}
+
+                impl ::bindb::Index for #idx_ident {
+                    type Table = #ident;
+                    const NAME: &'static str = #idx_name;
+                    type Key = #ty;
+                    type KeyRef<'a> = &'a #ty;
+
+                    fn get<'a>(t: &'a Self::Table) -> Self::KeyRef<'a> {
+                        &t.#member
+                    }
+                }
+            }
+        } else {
+            let idx_name = format!("{}_{}_{}", idx.name, idx.field.name, key.name);
+            quote! {
+                #vis struct #idx_ident {}
+
+                impl ::bindb::Index for #idx_ident {
+                    type Table = #ident;
+                    const NAME: &'static str = #idx_name;
+                    type Key = (#ty, #key_ty);
+                    type KeyRef<'a> = (&'a #ty, &'a #key_ty);
+
+                    fn get<'a>(t: &'a Self::Table) -> Self::KeyRef<'a> {
+                        (&t.#member, &t.#key_member)
+                    }
+                }
+            }
+        }
+    });
+
+    let codec = codec.map(|ty| quote! { type Codec = #ty; });
+    let version = version.map(|v| quote! { const VERSION: u32 = #v; });
+    let migrations = migrations.map(|path| quote! {
+        fn migrations() -> ::bindb::Migrations<Self> {
+            #path()
+        }
+    });
+
+    Ok(quote! {
+        impl ::bindb::Table for #ident {
+            const NAME: &'static str = #name;
+            type Key = #key_ty;
+            type Indices = (
+                #(#idx_idents,)*
+                #(::bindb::FullText<#text_idents>,)*
+                #(::bindb::Expires<#expiry_idents>,)*
+            );
+            type References = (#(#ref_idents,)*);
+            #codec
+            #version
+            #migrations
+
+            fn get(&self) -> &Self::Key {
+                &self.#key_member
+            }
+            fn get_mut(&mut self) -> &mut Self::Key {
+                &mut self.#key_member
+            }
+        }
+
+        #(#index_impls)*
+        #(#reference_impls)*
+        #(#text_impls)*
+        #expiry_impl
+    })
+}
@@ -0,0 +1,144 @@
+use crate::*;
+use heed::BytesEncode;
+
+const DEFAULT_MAX_OPS: usize = 50_000;
+const DEFAULT_MAX_BYTES: usize = 64 * 1024 * 1024;
+
+/// Writer for bulk loads. Writes go into a write transaction, which is committed after every `max_ops`
+/// operations or `max_bytes` bytes of written rows, and a new one is started.
+///
+/// The batch as a whole is not atomic, data written before the last chunk was committed stays in the database
+/// even if the batch is dropped without calling `commit`.
+pub struct Batch<'a> {
+    db: &'a Database,
+    tx: Option<Wtx<'a>>,
+    ops: usize,
+    bytes: usize,
+    max_ops: usize,
+    max_bytes: usize,
+}
+
+impl Database {
+    pub fn batch(&self) -> Batch<'_> {
+        Batch {
+            db: self,
+            tx: Some(self.wtx()),
+            ops: 0,
+            bytes: 0,
+            max_ops: DEFAULT_MAX_OPS,
+            max_bytes: DEFAULT_MAX_BYTES,
+        }
+    }
+}
+
+impl<'a> Batch<'a> {
+    pub fn max_ops(mut self, max_ops: usize) -> Self {
+        self.max_ops = max_ops;
+        self
+    }
+
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    fn wtx(&mut self) -> &mut Wtx<'a> {
+        self.tx.as_mut().unwrap()
+    }
+
+    fn written(&mut self, bytes: usize) {
+        self.ops += 1;
+        self.bytes += bytes;
+        if self.ops >= self.max_ops || self.bytes >= self.max_bytes {
+            self.flush();
+        }
+    }
+
+    /// Commit current chunk, and start a new one
+    pub fn flush(&mut self) {
+        if let Some(tx) = self.tx.take() {
+            tx.commit();
+        }
+        self.tx = Some(self.db.wtx());
+        self.ops = 0;
+        self.bytes = 0;
+    }
+
+    pub fn commit(mut self) {
+        if let Some(tx) = self.tx.take() {
+            tx.commit();
+        }
+    }
+
+    fn write<T: Table>(&mut self, v: &T, append: bool) {
+        let key = KeyType::<T>::bytes_encode(T::get(v)).unwrap();
+        let val = T::Codec::encode(v).unwrap();
+        let bytes = key.len() + val.len();
+
+        let db = self.db;
+        let raw = db.raw_db(T::NAME);
+        let wtx = self.wtx();
+        let change = watch::capture::<T>(db, &wtx.tx, T::get(v), Some(v));
+        if let Some(old) = db.typed_db::<T>().get(&wtx.tx, T::get(v)).unwrap() {
+            T::Indices::on_delete(db, &mut wtx.tx, &old);
+        }
+        if append {
+            // Appending fails if the key is not greater than the last one, LMDB keeps the transaction usable
+            match raw.append(&mut wtx.tx, &key, &val) {
+                Err(heed::Error::Mdb(heed::MdbError::KeyExist)) => raw.put(&mut wtx.tx, &key, &val).unwrap(),
+                other => other.unwrap(),
+            }
+        } else {
+            raw.put(&mut wtx.tx, &key, &val).unwrap();
+        }
+        T::Indices::on_insert(db, &mut wtx.tx, v);
+        wtx._on_write(change);
+
+        self.written(bytes);
+    }
+
+    pub fn put<T: Table>(&mut self, v: &T) {
+        self.write(v, false)
+    }
+
+    /// Put a row using LMDB append mode, which is much faster when rows come sorted by their serialized keys.
+    /// Rows out of order are inserted normally.
+    pub fn append<T: Table>(&mut self, v: &T) {
+        self.write(v, true)
+    }
+
+    /// Find and entry based on the index, if found, overwrite it and modify object id
+    pub fn put_by<I: Index>(&mut self, v: &mut I::Table)
+        where <<I as Index>::Table as Table>::Key: Clone
+    {
+        self.put_by_with::<I, _>(v, |old, v| {
+            *I::Table::get_mut(v) = I::Table::get(&old).clone();
+        })
+    }
+
+    /// Overwrite old entry using an index as key,
+    pub fn put_by_with<I, F>(&mut self, v: &mut I::Table, patch: F)
+        where I: Index, F: FnOnce(&I::Table, &mut I::Table)
+    {
+        if let Some(old) = self.get_by::<I>(I::get(v)) {
+            patch(&old, v);
+        }
+        self.put(v)
+    }
+
+    pub fn next_id<T: Table>(&mut self) -> Id {
+        self.wtx().next_id::<T>()
+    }
+
+    pub fn delete<T: Table>(&mut self, k: &T::Key) {
+        self.wtx().delete::<T>(k);
+        self.written(0);
+    }
+}
+
+impl<'a> ROps for Batch<'a> {
+    fn _ro_tx(&self) -> (&Database, &RoTxn) {
+        let wtx = self.tx.as_ref().unwrap();
+        (&wtx.db, &wtx.tx)
+    }
+}
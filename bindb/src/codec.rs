@@ -0,0 +1,87 @@
+use serde::{Serialize, de::DeserializeOwned};
+use heed::{BytesEncode, BytesDecode};
+use std::borrow::Cow;
+use std::marker::PhantomData;
+
+/// Error of a codec, which can be sent between threads and converted into `anyhow::Error`
+pub type BoxedError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Compression level used by the `Zstd` codec
+const ZSTD_LEVEL: i32 = 3;
+
+/// Format in which values of a table are stored.
+pub trait Codec {
+    fn encode<T: Serialize>(v: &T) -> Result<Vec<u8>, BoxedError>;
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, BoxedError>;
+}
+
+/// Self-describing and human readable, tolerates added optional fields. Default for all tables.
+pub struct Json;
+
+impl Codec for Json {
+    fn encode<T: Serialize>(v: &T) -> Result<Vec<u8>, BoxedError> {
+        Ok(serde_json::to_vec(v)?)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, BoxedError> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+/// Compact and fastest, but any change of the type requires a migration.
+pub struct Bincode;
+
+impl Codec for Bincode {
+    fn encode<T: Serialize>(v: &T) -> Result<Vec<u8>, BoxedError> {
+        Ok(bincode::serialize(v)?)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, BoxedError> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+}
+
+/// Self-describing binary format, a middle ground between `Json` and `Bincode`.
+pub struct Cbor;
+
+impl Codec for Cbor {
+    fn encode<T: Serialize>(v: &T) -> Result<Vec<u8>, BoxedError> {
+        Ok(serde_cbor::to_vec(v)?)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, BoxedError> {
+        Ok(serde_cbor::from_slice(bytes)?)
+    }
+}
+
+/// Zstd compressed output of another codec. Worth it for rows containing large text blobs.
+pub struct Zstd<C = Bincode>(PhantomData<C>);
+
+impl<C: Codec> Codec for Zstd<C> {
+    fn encode<T: Serialize>(v: &T) -> Result<Vec<u8>, BoxedError> {
+        Ok(zstd::encode_all(&C::encode(v)?[..], ZSTD_LEVEL)?)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, BoxedError> {
+        C::decode(&zstd::decode_all(bytes)?)
+    }
+}
+
+/// Adapter exposing a `Codec` to heed
+pub struct Val<C, T>(PhantomData<(C, T)>);
+
+impl<'a, C: Codec, T: Serialize + 'a> BytesEncode<'a> for Val<C, T> {
+    type EItem = T;
+
+    fn bytes_encode(item: &'a Self::EItem) -> Result<Cow<'a, [u8]>, Box<dyn std::error::Error>> {
+        C::encode(item).map(Cow::Owned).map_err(|e| e as Box<dyn std::error::Error>)
+    }
+}
+
+impl<'a, C: Codec, T: DeserializeOwned + 'a> BytesDecode<'a> for Val<C, T> {
+    type DItem = T;
+
+    fn bytes_decode(bytes: &'a [u8]) -> Result<Self::DItem, Box<dyn std::error::Error>> {
+        C::decode(bytes).map_err(|e| e as Box<dyn std::error::Error>)
+    }
+}
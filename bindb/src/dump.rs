@@ -0,0 +1,122 @@
+use crate::*;
+use crate::error::Result;
+use heed::CompactionOption;
+use serde::Deserialize;
+use std::io::{BufRead, Write};
+
+/// Type-erased operations on a registered table, which need to know its rust type
+#[derive(Clone, Copy)]
+pub(crate) struct TableOps {
+    dump: fn(&Tx, &mut dyn Write) -> Result<()>,
+    restore: fn(&mut Wtx, serde_json::Value) -> Result<()>,
+}
+
+impl TableOps {
+    pub(crate) fn of<T: Table + 'static>() -> Self {
+        TableOps {
+            dump: dump_table::<T>,
+            restore: restore_row::<T>,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct DumpLine<'a, T> {
+    table: &'a str,
+    row: &'a T,
+}
+
+#[derive(Serialize)]
+struct SequenceLine<'a> {
+    sequence: &'a str,
+    value: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RestoreLine {
+    Row { table: String, row: serde_json::Value },
+    Sequence { sequence: String, value: u64 },
+}
+
+fn dump_table<T: Table + 'static>(tx: &Tx, w: &mut dyn Write) -> Result<()> {
+    for row in tx.scan::<T>() {
+        serde_json::to_writer(&mut *w, &DumpLine { table: T::NAME, row: &row }).map_err(|e| Error::Codec(e.into()))?;
+        w.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+fn restore_row<T: Table + 'static>(tx: &mut Wtx, row: serde_json::Value) -> Result<()> {
+    let row = serde_json::from_value::<T>(row).map_err(|e| Error::Codec(e.into()))?;
+    tx.put(&row);
+    Ok(())
+}
+
+impl Database {
+    /// Copy the database file into `path`. The copy is made from a single read transaction, so it is
+    /// consistent even while the database is being written to. Compaction omits free pages.
+    pub fn backup_to(&self, path: impl AsRef<Path>, compact: bool) -> Result<()> {
+        let option = if compact { CompactionOption::Enabled } else { CompactionOption::Disabled };
+        self.tree.copy_to_path(path, option)?;
+        Ok(())
+    }
+
+    /// Write rows of every registered table as JSON lines in the form of `{"table": <NAME>, "row": <ROW>}`.
+    /// Tables are dumped in order of their names, from a single read transaction. They are followed by
+    /// the allocated sequences as `{"sequence": <NAME>, "value": <LAST>}`, so that `next_id` does not
+    /// return keys of the restored rows again.
+    pub fn dump(&self, mut w: impl Write) -> Result<()> {
+        let tx = self.tx();
+        for ops in self.tables.values() {
+            (ops.dump)(&tx, &mut w)?;
+        }
+        let meta = self.meta_db::<u64>();
+        for name in self.tables.keys() {
+            if let Some(value) = meta.get(&tx.tx, &sequence_key(name))? {
+                serde_json::to_writer(&mut w, &SequenceLine { sequence: name, value }).map_err(|e| Error::Codec(e.into()))?;
+                w.write_all(b"\n")?;
+            }
+        }
+        tx.commit();
+        w.flush()?;
+        Ok(())
+    }
+
+    /// Load rows written by `dump` into this database, which must not hold any rows yet. Restored rows
+    /// would otherwise take over unique index entries of existing ones. All tables present in the dump
+    /// need to be registered with the same schema. Sequences are only ever raised, keys already allocated
+    /// in this database are not handed out again.
+    pub fn restore(&self, r: impl BufRead) -> Result<()> {
+        let mut tx = self.wtx();
+        for table in self.tables.keys() {
+            if self.entries(&tx.tx, table)? > 0 {
+                return Err(Error::NotEmpty(table.to_string()));
+            }
+        }
+        let meta = self.meta_db::<u64>();
+        for line in r.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line).map_err(|e| Error::Codec(e.into()))? {
+                RestoreLine::Row { table, row } => {
+                    let ops = self.tables.get(table.as_str()).ok_or(Error::UnknownTable(table))?;
+                    (ops.restore)(&mut tx, row)?;
+                }
+                RestoreLine::Sequence { sequence, value } => {
+                    if !self.tables.contains_key(sequence.as_str()) {
+                        return Err(Error::UnknownTable(sequence));
+                    }
+                    let key = sequence_key(&sequence);
+                    if meta.get(&tx.tx, &key)?.unwrap_or(0) < value {
+                        meta.put(&mut tx.tx, &key, &value)?;
+                    }
+                }
+            }
+        }
+        tx.commit();
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Error reported by the underlying LMDB environment
+    Mdb(heed::MdbError),
+    /// Misuse of the environment detected by heed, such as opening a database with conflicting types
+    Heed(String),
+    Io(std::io::Error),
+    /// Stored bytes could not be converted to/from a rust value
+    Codec(crate::codec::BoxedError),
+    /// Database was written by a newer version of the table than the one being registered
+    SchemaTooNew { table: &'static str, stored: u32, current: u32 },
+    /// There is no migration which would bring the table to the required version
+    MissingMigration { table: &'static str, version: u32 },
+    /// Table of a read-only database is stored with an older schema version, and can't be migrated
+    NotMigrated { table: &'static str, stored: u32, current: u32 },
+    /// Data refers to a table, which was not registered
+    UnknownTable(String),
+    /// Dump can only be restored into a database without rows, but this table has some
+    NotEmpty(String),
+    /// Row can't be deleted, because it is referenced by rows of another table
+    Restricted { table: &'static str, referenced_by: &'static str },
+    /// Continuation token is not a valid cursor
+    InvalidCursor(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Mdb(e) => write!(f, "Database error: {}", e),
+            Error::Heed(e) => write!(f, "Database error: {}", e),
+            Error::Io(e) => write!(f, "IO error: {}", e),
+            Error::Codec(e) => write!(f, "Serialization error: {}", e),
+            Error::SchemaTooNew { table, stored, current } => write!(
+                f, "Table {} is stored with schema version {}, but this build only supports up to version {}",
+                table, stored, current
+            ),
+            Error::MissingMigration { table, version } => write!(
+                f, "Table {} has no migration to schema version {}", table, version
+            ),
+            Error::NotMigrated { table, stored, current } => write!(
+                f, "Table {} is stored with schema version {}, open it for writing to migrate it to version {}",
+                table, stored, current
+            ),
+            Error::UnknownTable(table) => write!(f, "Table {} is not registered", table),
+            Error::NotEmpty(table) => write!(f, "Table {} already has rows, restore needs an empty database", table),
+            Error::Restricted { table, referenced_by } => write!(
+                f, "Row of {} can't be deleted, it is referenced by {}", table, referenced_by
+            ),
+            Error::InvalidCursor(token) => write!(f, "Invalid cursor: {}", token),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Mdb(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::Codec(e) => Some(&**e),
+            _ => None,
+        }
+    }
+}
+
+impl Error {
+    /// Error of a heed codec, which is boxed without `Send + Sync`
+    pub(crate) fn heed_codec(e: Box<dyn std::error::Error>) -> Self {
+        Error::Codec(e.to_string().into())
+    }
+}
+
+/// heed boxes codec errors without `Send + Sync`, so they are kept as messages
+impl From<heed::Error> for Error {
+    fn from(e: heed::Error) -> Self {
+        match e {
+            heed::Error::Io(e) => Error::Io(e),
+            heed::Error::Mdb(e) => Error::Mdb(e),
+            heed::Error::Encoding(e) | heed::Error::Decoding(e) => Error::heed_codec(e),
+            e => Error::Heed(e.to_string()),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
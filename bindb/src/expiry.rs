@@ -0,0 +1,143 @@
+use crate::*;
+use crate::error::Result;
+use heed::BytesDecode;
+use serde::{Serializer, Deserialize, Deserializer};
+use std::collections::HashSet;
+use std::marker::PhantomData;
+use std::ops::Bound;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Field types holding the expiry time of a row, in seconds since unix epoch
+pub trait ExpiresAt {
+    fn expires_at(&self) -> u64;
+}
+
+impl ExpiresAt for u64 {
+    fn expires_at(&self) -> u64 {
+        *self
+    }
+}
+
+/// Rows without expiry time never expire
+impl<T: ExpiresAt> ExpiresAt for Option<T> {
+    fn expires_at(&self) -> u64 {
+        self.as_ref().map_or(u64::MAX, T::expires_at)
+    }
+}
+
+/// Time in seconds since unix epoch, leading the keys of expiry indices. Encoded in the same way as `Id`,
+/// big-endian in binary formats, so that index entries are ordered by time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Timestamp(pub u64);
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        Id(self.0).serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        Id::deserialize(d).map(|id| Timestamp(id.0))
+    }
+}
+
+/// Maintains expiry index `I` as a member of `Table::Indices`, and registers its table for `Database::sweep`.
+/// Key of the index starts with the expiry time as `Timestamp`.
+pub struct Expires<I>(PhantomData<I>);
+
+pub(crate) type SweepFn = fn(&Database, u64, Option<&[u8]>, usize) -> Result<(usize, Option<Vec<u8>>)>;
+
+impl<I: Index + 'static> IndexOps<I::Table> for Expires<I> {
+    fn on_register(db: Database) -> Database {
+        let mut db = <I as IndexOps<I::Table>>::on_register(db);
+        db.sweepers.insert(I::NAME, sweep_batch::<I>);
+        db
+    }
+
+    fn on_insert<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>, t: &I::Table) {
+        <I as IndexOps<I::Table>>::on_insert(db, tx, t)
+    }
+
+    fn on_delete<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>, t: &I::Table) {
+        <I as IndexOps<I::Table>>::on_delete(db, tx, t)
+    }
+
+    fn on_clear<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>) {
+        <I as IndexOps<I::Table>>::on_clear(db, tx)
+    }
+
+    fn db_name() -> &'static str {
+        I::NAME
+    }
+}
+
+/// Delete up to `limit` rows, which expired at `now` or earlier, following index key `after`, in a single
+/// transaction. Rows, which can't be deleted because of a `restrict` reference, are logged and skipped. A failed
+/// delete might have cascaded partially, so the transaction is retried without them. Returns the number of
+/// deleted rows, and the last visited index key if the batch was full, and more rows might have expired.
+fn sweep_batch<I: Index>(db: &Database, now: u64, after: Option<&[u8]>, limit: usize) -> Result<(usize, Option<Vec<u8>>)> {
+    let start = after.map_or(Bound::Unbounded, Bound::Excluded);
+    let end = bincode::serialize(&Timestamp(now.saturating_add(1))).unwrap();
+    let mut skipped = HashSet::new();
+
+    'batch: loop {
+        let mut tx = db.wtx();
+        let mut expired = db.raw_db(I::NAME)
+            .range(&tx.tx, &(start, Bound::Excluded(end.as_slice())))?
+            .take(limit)
+            .map(|r| r.map(|(k, pkey)| (k.to_vec(), pkey.to_vec())).map_err(Error::from))
+            .collect::<Result<Vec<_>>>()?;
+
+        let pending = expired.iter().map(|(_, pkey)| pkey).filter(|pkey| !skipped.contains(*pkey)).collect::<Vec<_>>();
+        for pkey in &pending {
+            let key = KeyType::<I::Table>::bytes_decode(pkey).map_err(Error::heed_codec)?;
+            match tx.try_delete::<I::Table>(&key) {
+                Ok(()) => {}
+                Err(Error::Restricted { table, referenced_by }) => {
+                    log::warn!(
+                        "Expired row of {} is kept, deleting it would delete a row of {} referenced by {}",
+                        <I::Table as Table>::NAME, table, referenced_by
+                    );
+                    skipped.insert(pkey.to_vec());
+                    continue 'batch;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        let deleted = pending.len();
+        tx.commit();
+
+        let last = if expired.len() == limit { expired.pop().map(|(k, _)| k) } else { None };
+        return Ok((deleted, last));
+    }
+}
+
+impl Database {
+    /// Delete rows of all tables with an expiry index, which expired at `now` or earlier. Rows are deleted
+    /// in transactions of at most `batch` rows, so writers are never blocked for long. Expired rows stay
+    /// visible until they are swept. Rows referenced through a `restrict` reference are logged and kept,
+    /// and are tried again by the next sweep. Returns the number of deleted rows, not counting cascaded deletes.
+    pub fn sweep(&self, now: u64, batch: usize) -> Result<usize> {
+        let batch = batch.max(1);
+        let mut deleted = 0;
+        for sweep in self.sweepers.values() {
+            let mut after = None;
+            loop {
+                let (n, last) = sweep(self, now, after.as_deref(), batch)?;
+                deleted += n;
+                match last {
+                    Some(last) => after = Some(last),
+                    None => break,
+                }
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// Delete rows, which are expired at the current time, see `Database::sweep`
+    pub fn sweep_expired(&self, batch: usize) -> Result<usize> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        self.sweep(now, batch)
+    }
+}
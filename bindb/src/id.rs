@@ -0,0 +1,35 @@
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use std::fmt;
+
+/// Compact key allocated from a per-table sequence, see `RwOps::next_id`.
+///
+/// Stored as big-endian bytes in binary formats, so that keys are ordered by allocation, and new rows are
+/// always appended at the end of the table. Human readable formats store it as a plain number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Id(pub u64);
+
+impl fmt::Display for Id {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Serialize for Id {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        if s.is_human_readable() {
+            s.serialize_u64(self.0)
+        } else {
+            self.0.to_be_bytes().serialize(s)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Id {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        if d.is_human_readable() {
+            u64::deserialize(d).map(Id)
+        } else {
+            <[u8; 8]>::deserialize(d).map(|b| Id(u64::from_be_bytes(b)))
+        }
+    }
+}
@@ -2,15 +2,50 @@
 #![feature(generic_associated_types)]
 #![feature(trace_macros)]
 
+// Allows derived code to refer to `::bindb` from within this crate
+extern crate self as bindb;
+
+mod error;
+mod schema;
+mod codec;
+mod dump;
+mod batch;
+mod watch;
+mod id;
+mod relation;
+mod stats;
+mod query;
+mod text;
+mod expiry;
+
+pub use error::Error;
+pub use codec::{Codec, Json, Bincode, Cbor, Zstd};
+pub use schema::Migrations;
+pub use batch::Batch;
+pub use watch::{Change, ChangeKind, ChangeInfo};
+pub use id::Id;
+pub use relation::{OnDelete, Reference, References};
+pub use stats::{Stats, TreeStats};
+pub use query::{Query, Page, Cursor};
+pub use text::{TextIndex, TextSource, FullText};
+pub use expiry::{ExpiresAt, Expires, Timestamp};
+
 use serde::{Serialize, de::DeserializeOwned};
-use std::path::Path;
-use std::collections::{HashMap};
-use heed::{RoTxn, RwTxn};
-use heed::types::{SerdeBincode, SerdeJson, CowSlice, DecodeIgnore};
+use std::path::{Path, PathBuf};
+use std::collections::{HashMap, BTreeMap};
+use heed::{RoTxn, RwTxn, BytesEncode};
+use heed::types::{SerdeBincode, CowSlice, DecodeIgnore, Str, ByteSlice};
+
+pub use bindb_derive::Table;
 
 type KeyType<T> = SerdeBincode<<T as Table>::Key>;
-type ValType<T> = SerdeJson<T>;
+type ValType<T> = codec::Val<<T as Table>::Codec, T>;
+
+/// Name of the internal database holding metadata, such as schema versions of the tables
+const META: &str = "__bindb_meta";
 
+/// Size of the memory map, and therefore the maximum size of the database file
+const MAP_SIZE: usize = 1024 * 1024 * 1024 * 1024;
 
 /// Types which should be stored.
 pub trait Table: Serialize + DeserializeOwned {
@@ -26,6 +61,20 @@ pub trait Table: Serialize + DeserializeOwned {
     fn get_mut(&mut self) -> &mut Self::Key;
 
     type Indices: Indices<Self> = ();
+
+    /// References from this table to other tables. These also need to be present in `Indices`.
+    type References: References<Self> = ();
+
+    /// Format of the stored values. Changing it requires a migration.
+    type Codec: Codec = Json;
+
+    /// Version of the stored representation. Increment it whenever the serialized form changes,
+    /// and provide a migration from the previous version in `migrations`.
+    const VERSION: u32 = 0;
+
+    fn migrations() -> Migrations<Self> {
+        Migrations::new()
+    }
 }
 
 
@@ -74,10 +123,55 @@ macro_rules! index {
     };
 }
 
+/// Maintenance of a single index of table `T`. Implemented for every `Index`, and for `FullText` and `Expires`
+/// indices.
+pub trait IndexOps<T> {
+    fn on_register(db: Database) -> Database;
+    fn on_insert<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>, t: &T);
+    fn on_delete<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>, t: &T);
+    fn on_clear<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>);
+    /// Name of the database holding the index
+    fn db_name() -> &'static str;
+}
+
+impl<I: Index> IndexOps<I::Table> for I {
+    fn on_register(db: Database) -> Database {
+        db.register_idx::<I>()
+    }
+
+    #[inline(always)]
+    fn on_insert<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>, t: &I::Table) {
+        let db_inner = db.index_db_ref::<I>();
+        db_inner.put(tx, &I::get(&t), I::Table::get(&t)).unwrap();
+    }
+
+    fn on_delete<'a>(db: &Database, tx: &mut RwTxn<'a, 'a, ()>, t: &I::Table) {
+        let inner_db = db.index_db_ref::<I>();
+        inner_db.delete(tx, &I::get(&t)).unwrap();
+    }
+
+    fn on_clear<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>) {
+        db.index_db::<I>().clear(tx).unwrap();
+    }
+
+    fn db_name() -> &'static str {
+        I::NAME
+    }
+}
+
 pub trait Indices<T> {
     fn on_register(db: Database) -> Database;
     fn on_insert<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>, t: &T);
     fn on_delete<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>, t: &T);
+    fn on_clear<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>);
+    /// Names of the indices, which were not recorded as built yet
+    fn unbuilt(db: &Database, tx: &RoTxn) -> Vec<&'static str>;
+    /// Clear the indices named in `names`, before they are filled by `insert_into`
+    fn clear_some<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>, names: &[&str]);
+    /// Insert a row into the indices named in `names` only
+    fn insert_into<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>, t: &T, names: &[&str]);
+    /// Record all indices as built
+    fn mark_built<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>);
 }
 
 #[impl_trait_for_tuples::impl_for_tuples(6)]
@@ -85,26 +179,43 @@ pub trait Indices<T> {
 impl<T> Indices<T> for Tuple
     where T: Table,
 {
-    for_tuples!(where #(Tuple: Index<Table=T>)*);
+    for_tuples!(where #(Tuple: IndexOps<T>)*);
 
     fn on_register(mut db: Database) -> Database {
-        for_tuples!( #( db = db.register_idx::<Tuple>();)* );
+        for_tuples!( #( db = Tuple::on_register(db);)* );
         db
     }
 
     #[inline(always)]
     fn on_insert<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>, t: &T) {
-        for_tuples!( #(
-            let db_inner = db.index_db_ref::<Tuple>();
-            db_inner.put(tx, &Tuple::get(&t), Tuple::Table::get(&t)).unwrap();
-        )*);
+        for_tuples!( #( Tuple::on_insert(db, tx, t); )* );
     }
 
     fn on_delete<'a>(db: &Database, tx: &mut RwTxn<'a, 'a, ()>, t: &T) {
-        for_tuples!( #(
-            let inner_db = db.index_db_ref::<Tuple>();
-            inner_db.delete(tx, &Tuple::get(&t)).unwrap();
-        )*);
+        for_tuples!( #( Tuple::on_delete(db, tx, t); )* );
+    }
+
+    fn on_clear<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>) {
+        for_tuples!( #( Tuple::on_clear(db, tx); )* );
+    }
+
+    #[allow(unused_mut)]
+    fn unbuilt(db: &Database, tx: &RoTxn) -> Vec<&'static str> {
+        let mut names = vec![];
+        for_tuples!( #( if !schema::is_built(db, tx, Tuple::db_name()) { names.push(Tuple::db_name()); } )* );
+        names
+    }
+
+    fn clear_some<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>, names: &[&str]) {
+        for_tuples!( #( if names.contains(&Tuple::db_name()) { Tuple::on_clear(db, tx); } )* );
+    }
+
+    fn insert_into<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>, t: &T, names: &[&str]) {
+        for_tuples!( #( if names.contains(&Tuple::db_name()) { Tuple::on_insert(db, tx, t); } )* );
+    }
+
+    fn mark_built<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>) {
+        for_tuples!( #( schema::set_built(db, tx, Tuple::db_name()); )* );
     }
 }
 
@@ -112,10 +223,32 @@ impl<T> Indices<T> for Tuple
 pub struct Database {
     tree: heed::Env,
     dbs: HashMap<String, heed::UntypedDatabase>,
+    tables: BTreeMap<&'static str, dump::TableOps>,
+    listeners: watch::Listeners,
+    relations: HashMap<&'static str, Vec<relation::RelationOps>>,
+    sweepers: BTreeMap<&'static str, expiry::SweepFn>,
+    path: PathBuf,
+    read_only: bool,
+    // Declared last, so that the environment is closed before the file is removed. Shared by clones,
+    // which use the same environment
+    temp: Option<std::sync::Arc<TempFile>>,
+}
+
+/// Database file, which is removed together with its lock file on drop
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let mut lock = self.0.clone().into_os_string();
+        lock.push("-lock");
+        let _ = std::fs::remove_file(&self.0);
+        let _ = std::fs::remove_file(lock);
+    }
 }
 
 impl Database {
     pub fn open(f: impl AsRef<Path>) -> Self {
+        let path = f.as_ref().to_path_buf();
         unsafe {
             std::fs::OpenOptions::new()
                 .create(true)
@@ -127,30 +260,131 @@ impl Database {
             let db = heed::EnvOpenOptions::new()
                 .max_dbs(256)
                 .max_readers(32)
-                .map_size(1024 * 1024 * 1024 * 1024)
+                .map_size(MAP_SIZE)
                 .flag(heed::flags::Flags::MdbNoSubDir)
                 .open(f)
                 .unwrap();
 
+            let mut dbs = HashMap::new();
+            dbs.insert(META.to_string(), db.create_database(Some(META)).unwrap());
+
             Database {
                 tree: db,
-                dbs: HashMap::new(),
+                dbs,
+                tables: BTreeMap::new(),
+                listeners: Default::default(),
+                relations: HashMap::new(),
+                sweepers: BTreeMap::new(),
+                path,
+                read_only: false,
+                temp: None,
             }
         }
     }
 
-    pub fn register<T: Table>(mut self) -> Self {
-        let db = self.tree.create_database(Some(T::NAME)).unwrap();
+    /// Open an existing database without modifying it, fails if the file does not exist. Nothing is
+    /// created or migrated, so tables need to be registered at their stored schema version, and tables
+    /// or indices missing in the file are skipped.
+    pub fn open_read_only(f: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = f.as_ref().to_path_buf();
+        if !path.is_file() {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Database {} does not exist", path.display()),
+            )));
+        }
+        let db = unsafe {
+            heed::EnvOpenOptions::new()
+                .max_dbs(256)
+                .max_readers(32)
+                .map_size(MAP_SIZE)
+                .flag(heed::flags::Flags::MdbNoSubDir)
+                .flag(heed::flags::Flags::MdbRdOnly)
+                .open(&path)?
+        };
+
+        let mut dbs = HashMap::new();
+        let meta = db.open_database(Some(META))?.ok_or_else(|| Error::UnknownTable(META.to_string()))?;
+        dbs.insert(META.to_string(), meta);
+
+        Ok(Database {
+            tree: db,
+            dbs,
+            tables: BTreeMap::new(),
+            listeners: Default::default(),
+            relations: HashMap::new(),
+            sweepers: BTreeMap::new(),
+            path,
+            read_only: true,
+            temp: None,
+        })
+    }
+
+    /// Open a new empty database in a uniquely named file in the temporary directory. The file is
+    /// removed when the database is dropped, so tests using it don't share state and can run in parallel.
+    pub fn temporary() -> Self {
+        let path = std::env::temp_dir().join(format!("bindb-{}.mdb", uuid::Uuid::new_v4()));
+        let mut db = Self::open(&path);
+        db.temp = Some(std::sync::Arc::new(TempFile(path)));
+        db
+    }
+
+    /// Path of the database file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Register a table and its indices, migrating stored rows to the current `Table::VERSION`.
+    /// Panics if the migration fails, use `try_register` to handle the error.
+    pub fn register<T: Table + 'static>(self) -> Self {
+        self.try_register::<T>().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_register<T: Table + 'static>(mut self) -> Result<Self, Error> {
+        if self.read_only {
+            return match self.tree.open_database(Some(T::NAME))? {
+                Some(db) => {
+                    self.dbs.insert(T::NAME.to_string(), db);
+                    self.tables.insert(T::NAME, dump::TableOps::of::<T>());
+                    let db = T::References::on_register(T::Indices::on_register(self));
+                    schema::check::<T>(&db)?;
+                    Ok(db)
+                }
+                None => Ok(self),
+            };
+        }
+        let db = self.open_or_create(T::NAME)?;
         self.dbs.insert(T::NAME.to_string(), db);
-        T::Indices::on_register(self)
+        self.tables.insert(T::NAME, dump::TableOps::of::<T>());
+        let db = T::Indices::on_register(self);
+        let db = T::References::on_register(db);
+        schema::migrate::<T>(&db)?;
+        Ok(db)
     }
 
-    pub fn register_idx<I: Index>(mut self) -> Self {
-        let db = self.tree.create_database(Some(I::NAME)).unwrap();
-        self.dbs.insert(I::NAME.to_string(), db);
+    pub fn register_idx<I: Index>(self) -> Self {
+        self.create_db(I::NAME)
+    }
+
+    pub(crate) fn create_db(mut self, name: &str) -> Self {
+        if self.read_only {
+            let db = self.tree.open_database(Some(name)).unwrap();
+            self.dbs.extend(db.map(|db| (name.to_string(), db)));
+            return self;
+        }
+        let db = self.open_or_create(name).unwrap();
+        self.dbs.insert(name.to_string(), db);
         self
     }
 
+    /// Creating a database takes a write transaction, existing ones are opened with a read transaction only
+    fn open_or_create(&self, name: &str) -> Result<heed::UntypedDatabase, Error> {
+        match self.tree.open_database(Some(name))? {
+            Some(db) => Ok(db),
+            None => Ok(self.tree.create_database(Some(name))?),
+        }
+    }
+
     pub fn tx(&self) -> Tx<'_> {
         Tx {
             db: self,
@@ -169,6 +403,8 @@ impl Database {
         Wtx {
             db: self,
             tx: self.tree.write_txn().unwrap(),
+            dirty: false,
+            changes: vec![],
         }
     }
 
@@ -185,7 +421,7 @@ impl Database {
     pub fn untyped_db<T : Table>(&self) -> heed::Database<DecodeIgnore, DecodeIgnore> {
         self.dbs.get(T::NAME).expect("Table not registered").remap_types()
     }
-    pub fn typed_db<T: Table>(&self) -> heed::Database<SerdeBincode<T::Key>, SerdeJson<T>> {
+    pub fn typed_db<T: Table>(&self) -> heed::Database<KeyType<T>, ValType<T>> {
         self.dbs.get(T::NAME).expect("Table not registered").remap_types()
     }
     pub fn index_db<I: Index>(&self) -> heed::Database<SerdeBincode<I::Key>, SerdeBincode<<I::Table as Table>::Key>> {
@@ -195,6 +431,14 @@ impl Database {
         self.dbs.get(I::NAME).expect("Index not registered").remap_types()
     }
 
+    pub(crate) fn raw_db(&self, name: &str) -> heed::Database<ByteSlice, ByteSlice> {
+        self.dbs.get(name).expect("Table not registered").remap_types()
+    }
+
+    pub(crate) fn meta_db<V: Serialize + DeserializeOwned>(&self) -> heed::Database<Str, SerdeBincode<V>> {
+        self.dbs.get(META).unwrap().remap_types()
+    }
+
     pub fn generate_id(&self) -> uuid::Uuid {
         uuid::Uuid::new_v4()
     }
@@ -212,6 +456,51 @@ impl<'a, T: Table + 'static> Iterator for Iter<'a, T> {
     }
 }
 
+pub struct Keys<'a, T: Table> {
+    i: heed::RoRange<'a, KeyType<T>, DecodeIgnore>,
+}
+
+impl<'a, T: Table + 'static> Iterator for Keys<'a, T> {
+    type Item = T::Key;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.i.next().map(|v| v.unwrap().0)
+    }
+}
+
+/// Row with a decoded key, whose value is decoded only on demand
+pub struct Entry<'a, T: Table> {
+    pub key: T::Key,
+    bytes: &'a [u8],
+    _t: std::marker::PhantomData<T>,
+}
+
+impl<'a, T: Table> Entry<'a, T> {
+    pub fn value(&self) -> T {
+        T::Codec::decode(self.bytes).unwrap()
+    }
+
+    /// Value encoded with the codec of the table
+    pub fn raw(&self) -> &'a [u8] {
+        self.bytes
+    }
+}
+
+pub struct Entries<'a, T: Table> {
+    i: heed::RoRange<'a, KeyType<T>, ByteSlice>,
+}
+
+impl<'a, T: Table + 'static> Iterator for Entries<'a, T> {
+    type Item = Entry<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.i.next().map(|v| {
+            let (key, bytes) = v.unwrap();
+            Entry { key, bytes, _t: std::marker::PhantomData }
+        })
+    }
+}
+
 pub trait ROps {
     fn _ro_tx(&self) -> (&Database, &RoTxn);
 
@@ -248,16 +537,106 @@ pub trait ROps {
             i: r
         }
     }
+
+    /// Scan primary keys of a table, without decoding the values
+    fn scan_keys<T: Table + 'static>(&self) -> Keys<T> {
+        let (db, tx) = self._ro_tx();
+        let db = db.typed_db::<T>().remap_data_type::<DecodeIgnore>();
+
+        Keys {
+            i: db.range(tx, &(..)).unwrap()
+        }
+    }
+
+    /// Scan a table, decoding the values only when `Entry::value` is called
+    fn scan_entries<T: Table + 'static>(&self) -> Entries<T> {
+        let (db, tx) = self._ro_tx();
+        let db = db.typed_db::<T>().remap_data_type::<ByteSlice>();
+
+        Entries {
+            i: db.range(tx, &(..)).unwrap()
+        }
+    }
+
+    /// Row with the greatest primary key starting with the encoded `prefix`, such as the newest row of a
+    /// composite key ending with an `Id`. The table is read backwards from the end of the prefix, one row only.
+    fn last_with_prefix<T: Table, P: Serialize>(&self, prefix: &P) -> Option<T> {
+        let (db, tx) = self._ro_tx();
+        let prefix = bincode::serialize(prefix).unwrap();
+        let mut rows = db.raw_db(T::NAME).rev_prefix_iter(tx, &prefix).unwrap();
+        rows.next().map(|r| T::Codec::decode::<T>(r.unwrap().1).unwrap())
+    }
+
+    /// Number of rows in a table. This is `ms_entries` of `mdb_stat`, constant time, no rows are read.
+    fn count<T: Table>(&self) -> usize {
+        let (db, tx) = self._ro_tx();
+        db.entries(tx, T::NAME).unwrap() as usize
+    }
+
+    /// Primary keys of rows, which reference the `parent` row through reference `R`
+    fn referencing<R: Reference>(&self, parent: &<R::Parent as Table>::Key) -> Vec<<R::Table as Table>::Key> {
+        let (db, tx) = self._ro_tx();
+        let prefix = KeyType::<R::Parent>::bytes_encode(parent).unwrap();
+        let idb = db.raw_db(R::NAME).remap_data_type::<KeyType<R::Table>>();
+
+        let res = idb.prefix_iter(tx, &prefix).unwrap().map(|v| v.unwrap().1).collect();
+        res
+    }
+
+    /// Up to `limit` rows of table `T` in primary key order, following the cursor. Start from a known key
+    /// with `Cursor::after`, and continue with `Page::next`. Fails if the cursor belongs to another table.
+    fn scan_from<T: Table>(&self, after: Option<&Cursor>, limit: usize) -> Result<Page<T>, Error> {
+        let (db, tx) = self._ro_tx();
+        query::page(db, tx, T::NAME, after, limit, |_, val| Some(T::Codec::decode::<T>(val).unwrap()))
+    }
+
+    /// Up to `limit` rows of the table of index `I`, ordered by the index key, following the cursor.
+    /// Start from a known index key with `Cursor::after_index`, and continue with `Page::next`.
+    /// Fails if the cursor belongs to another table or index.
+    fn scan_index_from<I: Index>(&self, after: Option<&Cursor>, limit: usize) -> Result<Page<I::Table>, Error> {
+        let (db, tx) = self._ro_tx();
+        let table = db.raw_db(<I::Table as Table>::NAME);
+        query::page(db, tx, I::NAME, after, limit, |_, pkey| {
+            table.get(tx, pkey).unwrap().map(|val| <I::Table as Table>::Codec::decode(val).unwrap())
+        })
+    }
+
+    /// Up to `limit` rows containing all words of the query in text indexed by `X`, best matches first.
+    /// Every word of the query also matches longer words starting with it.
+    fn search<X: TextIndex>(&self, query: &str, limit: usize) -> Vec<X::Table> {
+        let (db, tx) = self._ro_tx();
+        let table = db.raw_db(<X::Table as Table>::NAME);
+        text::search::<X>(db, tx, query)
+            .into_iter()
+            .filter_map(|(_, pkey)| table.get(tx, &pkey).unwrap())
+            .take(limit)
+            .map(|val| <X::Table as Table>::Codec::decode(val).unwrap())
+            .collect()
+    }
+
+    /// Start a query over rows of table `T`
+    fn query<T: Table + 'static>(&self) -> Query<T> {
+        let (db, tx) = self._ro_tx();
+        Query::new(db, tx)
+    }
 }
 
 pub trait RwOps<'a>: ROps {
     fn _rw_tx(&mut self) -> (&Database, &mut RwTxn<'a, 'a>);
+    /// Called after every modification, with the captured change if the table is being watched
+    fn _on_write(&mut self, change: Option<watch::PendingChange>);
 
     fn put<T: Table>(&mut self, v: &T) {
         let (dd, mut tx) = self._rw_tx();
+        let change = watch::capture::<T>(dd, tx, T::get(v), Some(v));
         let db = dd.typed_db::<T>();
+        // Index entries of the overwritten row might differ from the new ones
+        if let Some(old) = db.get(tx, T::get(v)).unwrap() {
+            T::Indices::on_delete(dd, tx, &old);
+        }
         db.put(&mut tx, &T::get(&v), &v).unwrap();
         T::Indices::on_insert(&dd, &mut tx, &v);
+        self._on_write(change);
     }
 
     /// Find and entry based on the index, if found, overwrite it and modify object id
@@ -279,18 +658,40 @@ pub trait RwOps<'a>: ROps {
         self.put(v)
     }
 
+    /// Delete a row, panics if it is referenced with `OnDelete::Restrict`
     fn delete<T: Table>(&mut self, k: &T::Key) {
-        let (db, mut tx) = self._rw_tx();
-        let typed = db.typed_db::<T>();
+        self.try_delete::<T>(k).unwrap_or_else(|e| panic!("{}", e))
+    }
 
-        if let Some(item) = typed.get(&tx, k).unwrap() {
-            // If the entry was stored, first update index table and only after that delete the entry
-            T::Indices::on_delete(&db, &mut tx, &item);
+    /// Delete a row, and apply `OnDelete` actions of references pointing to it. If this fails, some
+    /// of the cascaded deletes might have been applied already, and the transaction should be aborted.
+    fn try_delete<T: Table>(&mut self, k: &T::Key) -> Result<(), Error> {
+        let (db, tx) = self._rw_tx();
+        let mut changes = vec![];
+        let res = relation::delete::<T>(db, tx, k, &mut changes);
+        for change in changes {
+            self._on_write(change);
         }
-        typed.delete(&mut tx, k).unwrap();
+        res
+    }
+
+    /// Allocate next value from the sequence of table `T`, starting at 1. The sequence is stored
+    /// in the database, and the allocation is rolled back if this transaction is not committed.
+    fn next_id<T: Table>(&mut self) -> Id {
+        let (db, tx) = self._rw_tx();
+        let meta = db.meta_db::<u64>();
+        let key = sequence_key(T::NAME);
+
+        let next = meta.get(tx, &key).unwrap().unwrap_or(0) + 1;
+        meta.put(tx, &key, &next).unwrap();
+        Id(next)
     }
 }
 
+fn sequence_key(name: &str) -> String {
+    format!("sequence.{}", name)
+}
+
 pub struct Tx<'a> {
     db: &'a Database,
     tx: RoTxn<'a>,
@@ -311,11 +712,20 @@ impl<'a> ROps for Tx<'a> {
 pub struct Wtx<'a> {
     db: &'a Database,
     tx: RwTxn<'a, 'a>,
+    dirty: bool,
+    changes: Vec<watch::PendingChange>,
 }
 
 impl<'a> Wtx<'a> {
-    pub fn commit(self) {
+    /// Commit the transaction and notify listeners about changes made in it
+    pub fn commit(mut self) {
+        if !self.dirty {
+            self.tx.commit().unwrap();
+            return;
+        }
+        let seq = watch::bump_seq(self.db, &mut self.tx);
         self.tx.commit().unwrap();
+        self.db.notify(seq, self.changes);
     }
 }
 
@@ -330,33 +740,9 @@ impl<'a> RwOps<'a> for Wtx<'a> {
     fn _rw_tx(&mut self) -> (&Database, &mut RwTxn<'a, 'a>) {
         (&self.db, &mut self.tx)
     }
-}
 
-#[test]
-fn test_simple() {
-    use serde::{Serialize, Deserialize};
-    use crate::{ROps, RwOps};
-
-    #[derive(Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
-    struct Item(usize, usize);
-    table!(Item => 0(usize));
-
-    let mut db = Database::open("/tmp/db").register::<Item>();
-    {
-        let mut db = db.wtx();
-        db.put(&Item(0, 0));
-        db.put(&Item(1, 0));
-        db.put(&Item(2, 0));
-        db.put(&Item(4, 0));
-        db.put(&Item(0, 0));
-        db.commit();
-    }
-    let mut db = db.wtx();
-    assert_eq!(db.get(&2), Some(Item(2, 0)));
-
-    let range = db.scan::<Item>();
-    assert_eq!(range.count(), 4);
-
-    db.delete::<Item>(&0);
-    assert_eq!(db.scan::<Item>().count(), 3);
+    fn _on_write(&mut self, change: Option<watch::PendingChange>) {
+        self.dirty = true;
+        self.changes.extend(change);
+    }
 }
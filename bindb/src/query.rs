@@ -0,0 +1,326 @@
+use crate::*;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::rc::Rc;
+use std::ops::Bound;
+use std::str::FromStr;
+use serde::{Deserialize, Deserializer, Serializer};
+
+/// Position in a table or an index, after which the next page of results starts. It is serialized as a string
+/// token, which stays valid across transactions, rows inserted or deleted in the meantime are simply seen or
+/// skipped. The token names the table or index it was created for, and is rejected by scans of any other.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Cursor {
+    source: String,
+    key: Vec<u8>,
+}
+
+impl Cursor {
+    /// Cursor positioned after a primary key of table `T`
+    pub fn after<T: Table>(key: &T::Key) -> Self {
+        Cursor { source: T::NAME.to_string(), key: KeyType::<T>::bytes_encode(key).unwrap().into_owned() }
+    }
+
+    /// Cursor positioned after a key of index `I`
+    pub fn after_index<'a, I: Index>(key: I::KeyRef<'a>) -> Self {
+        Cursor { source: I::NAME.to_string(), key: bincode::serialize(&key).unwrap() }
+    }
+
+    /// Encoded key of the cursor, if it was created for table or index `source`
+    pub(crate) fn key_in<'c>(after: Option<&'c Cursor>, source: &str) -> Result<Option<&'c [u8]>, Error> {
+        match after {
+            Some(c) if c.source != source => Err(Error::InvalidCursor(c.to_string())),
+            Some(c) => Ok(Some(&c.key)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.", self.source)?;
+        for b in &self.key {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidCursor(s.to_string());
+        let (source, key) = s.rsplit_once('.').ok_or_else(invalid)?;
+        if source.is_empty() || key.len() % 2 != 0 || !key.is_ascii() {
+            return Err(invalid());
+        }
+        let key = (0..key.len()).step_by(2)
+            .map(|i| u8::from_str_radix(&key[i..i + 2], 16))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| invalid())?;
+        Ok(Cursor { source: source.to_string(), key })
+    }
+}
+
+impl Serialize for Cursor {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Cursor {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        String::deserialize(d)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Single page of query results
+#[derive(Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Cursor of the next page, `None` if this is the last one
+    pub next: Option<Cursor>,
+}
+
+/// Page of up to `limit` entries of a raw database following the cursor, each entry converted to a result by `row`.
+/// Entries, for which `row` returns `None`, are skipped.
+pub(crate) fn page<'t, R>(
+    db: &Database, tx: &'t RoTxn, name: &str, after: Option<&Cursor>, limit: usize,
+    mut row: impl FnMut(&'t [u8], &'t [u8]) -> Option<R>,
+) -> Result<Page<R>, Error> {
+    assert!(limit > 0, "Page limit must be positive");
+    let start = Cursor::key_in(after, name)?.map_or(Bound::Unbounded, Bound::Excluded);
+    let mut items = vec![];
+    let mut last: Option<&[u8]> = None;
+    let mut next = None;
+
+    for entry in db.raw_db(name).range(tx, &(start, Bound::Unbounded)).unwrap() {
+        let (key, val) = entry.unwrap();
+        if let Some(r) = row(key, val) {
+            if items.len() == limit {
+                next = last.map(|k| Cursor { source: name.to_string(), key: k.to_vec() });
+                break;
+            }
+            last = Some(key);
+            items.push(r);
+        }
+    }
+    Ok(Page { items, next })
+}
+
+type Filter<'a, T> = Box<dyn Fn(&T) -> bool + 'a>;
+type Keys<'s> = Box<dyn Iterator<Item=Vec<u8>> + 's>;
+
+/// Single restriction of a query
+enum Term<T> {
+    /// Key of the row in index `name` starts with `prefix`
+    Index { name: &'static str, prefix: Vec<u8>, key_of: fn(&T) -> Vec<u8> },
+    /// Encoded primary key starts with the prefix
+    KeyPrefix(Vec<u8>),
+    /// Encoded primary key is one of the keys
+    Keys(Rc<BTreeSet<Vec<u8>>>),
+}
+
+impl<T> Clone for Term<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Term::Index { name, prefix, key_of } => Term::Index { name, prefix: prefix.clone(), key_of: *key_of },
+            Term::KeyPrefix(prefix) => Term::KeyPrefix(prefix.clone()),
+            Term::Keys(keys) => Term::Keys(keys.clone()),
+        }
+    }
+}
+
+fn index_key<I: Index>(t: &I::Table) -> Vec<u8> {
+    bincode::serialize(&I::get(t)).unwrap()
+}
+
+impl<T: Table> Term<T> {
+    fn holds(&self, key: &[u8], row: &T) -> bool {
+        match self {
+            Term::Index { prefix, key_of, .. } => key_of(row).starts_with(prefix),
+            Term::KeyPrefix(prefix) => key.starts_with(prefix),
+            Term::Keys(keys) => keys.contains(key),
+        }
+    }
+
+    /// Index entries are ordered by the index key, so they are the last choice to enumerate rows from
+    fn is_ordered(&self) -> bool {
+        !matches!(self, Term::Index { .. })
+    }
+
+    /// Ascending encoded primary keys of rows, for which this term might hold, following `after`
+    fn candidates<'s>(&'s self, db: &'s Database, tx: &'s RoTxn, after: Option<&'s [u8]>) -> Keys<'s> {
+        let after_bound = after.map_or(Bound::Unbounded, Bound::Excluded);
+        match self {
+            Term::Index { name, prefix, .. } => {
+                let keys = db.raw_db(name).prefix_iter(tx, prefix).unwrap()
+                    .map(|r| r.unwrap().1)
+                    .filter(|k| after < Some(*k))
+                    .map(<[u8]>::to_vec)
+                    .collect::<BTreeSet<_>>();
+                Box::new(keys.into_iter())
+            }
+            Term::KeyPrefix(prefix) => {
+                let start = match after {
+                    Some(a) if a >= prefix.as_slice() => after_bound,
+                    _ => Bound::Included(prefix.as_slice()),
+                };
+                Box::new(db.raw_db(T::NAME).range(tx, &(start, Bound::Unbounded)).unwrap()
+                    .map(|r| r.unwrap().0)
+                    .take_while(move |k| k.starts_with(prefix))
+                    .map(<[u8]>::to_vec))
+            }
+            Term::Keys(keys) => Box::new(keys.range::<[u8], _>((after_bound, Bound::Unbounded)).cloned()),
+        }
+    }
+}
+
+/// Union of ascending sequences of keys, without duplicates
+fn union<'s>(sources: Vec<Keys<'s>>) -> Keys<'s> {
+    let mut sources = sources.into_iter().map(Iterator::peekable).collect::<Vec<_>>();
+    Box::new(std::iter::from_fn(move || {
+        let min = sources.iter_mut().filter_map(|s| s.peek().cloned()).min()?;
+        for source in &mut sources {
+            if source.peek() == Some(&min) {
+                source.next();
+            }
+        }
+        Some(min)
+    }))
+}
+
+/// Query over rows of a single table. Rows can be restricted by several indices, whose results are intersected
+/// or united, and by arbitrary predicates. Results are returned in primary key order.
+///
+/// Nothing is evaluated until the rows are fetched. Each restriction is then checked against the rows
+/// themselves, and only one restriction of every alternative is used to enumerate candidate rows, preferably
+/// one, which yields them in primary key order, such as `key_prefix`. Rows of an index restriction are
+/// collected only from the entries following the cursor of the requested page.
+pub struct Query<'a, T: Table> {
+    db: &'a Database,
+    tx: &'a RoTxn<'a>,
+    /// Matching rows are those satisfying all terms of any alternative. No alternatives match all rows.
+    alternatives: Vec<Vec<Term<T>>>,
+    filters: Vec<Filter<'a, T>>,
+}
+
+impl<'a, T: Table + 'static> Query<'a, T> {
+    pub(crate) fn new(db: &'a Database, tx: &'a RoTxn<'a>) -> Self {
+        Query { db, tx, alternatives: vec![], filters: vec![] }
+    }
+
+    fn index_term<I: Index<Table=T>, P: Serialize>(prefix: &P) -> Term<T> {
+        Term::Index { name: I::NAME, prefix: bincode::serialize(prefix).unwrap(), key_of: index_key::<I> }
+    }
+
+    fn intersect(mut self, term: Term<T>) -> Self {
+        if self.alternatives.is_empty() {
+            self.alternatives.push(vec![term]);
+        } else {
+            for alternative in &mut self.alternatives {
+                alternative.push(term.clone());
+            }
+        }
+        self
+    }
+
+    /// Keep only rows, whose key in index `I` starts with the encoded `prefix`. The prefix is a value of the
+    /// indexed field for non-unique indices, or any number of leading fields of the index key, which are
+    /// compared for equality. Strings are encoded with their length, so a part of a string matches nothing.
+    pub fn by<I: Index<Table=T>, P: Serialize>(self, prefix: &P) -> Self {
+        self.intersect(Self::index_term::<I, P>(prefix))
+    }
+
+    /// Add rows, whose key in index `I` starts with the encoded `prefix`, to the rows matched by all previous
+    /// restrictions. Has no effect, if the query was not restricted yet, since it matches all rows.
+    pub fn or_by<I: Index<Table=T>, P: Serialize>(mut self, prefix: &P) -> Self {
+        if !self.alternatives.is_empty() {
+            self.alternatives.push(vec![Self::index_term::<I, P>(prefix)]);
+        }
+        self
+    }
+
+    /// Keep only rows, whose primary key starts with `prefix`, such as the leading fields of a composite key
+    pub fn key_prefix<P: Serialize>(self, prefix: &P) -> Self {
+        self.intersect(Term::KeyPrefix(bincode::serialize(prefix).unwrap()))
+    }
+
+    /// Keep only rows containing all words of the text query in index `X`, see `ROps::search`.
+    /// Results are still returned in primary key order.
+    pub fn matching<X: TextIndex<Table=T>>(self, query: &str) -> Self {
+        let keys = text::search::<X>(self.db, self.tx, query).into_iter().map(|(_, pkey)| pkey).collect();
+        self.intersect(Term::Keys(Rc::new(keys)))
+    }
+
+    /// Keep only rows with given primary keys, such as keys found through a reference in another table
+    pub fn keys(self, keys: impl IntoIterator<Item=T::Key>) -> Self {
+        let keys = keys.into_iter()
+            .map(|k| KeyType::<T>::bytes_encode(&k).unwrap().into_owned())
+            .collect();
+        self.intersect(Term::Keys(Rc::new(keys)))
+    }
+
+    /// Keep only rows matching the predicate
+    pub fn filter(mut self, f: impl Fn(&T) -> bool + 'a) -> Self {
+        self.filters.push(Box::new(f));
+        self
+    }
+
+    /// Encoded keys and rows matching the restrictions, after the encoded key `after`
+    fn rows<'s>(&'s self, after: Option<&'s [u8]>) -> Box<dyn Iterator<Item=(Vec<u8>, T)> + 's> {
+        let raw = self.db.raw_db(T::NAME);
+        let decode = |val: &[u8]| T::Codec::decode::<T>(val).unwrap();
+        if self.alternatives.is_empty() {
+            let start = after.map_or(Bound::Unbounded, Bound::Excluded);
+            return Box::new(raw.range(self.tx, &(start, Bound::Unbounded)).unwrap()
+                .map(move |r| r.map(|(k, v)| (k.to_vec(), decode(v))).unwrap()));
+        }
+
+        let candidates = self.alternatives.iter()
+            .map(|terms| {
+                let driver = terms.iter().find(|t| t.is_ordered()).unwrap_or(&terms[0]);
+                driver.candidates(self.db, self.tx, after)
+            })
+            .collect();
+        Box::new(union(candidates)
+            .filter_map(move |key| raw.get(self.tx, &key).unwrap().map(|v| (key, decode(v))))
+            .filter(move |(key, row)| self.alternatives.iter().any(|terms| terms.iter().all(|t| t.holds(key, row)))))
+    }
+
+    fn matches(&self, row: &T) -> bool {
+        self.filters.iter().all(|f| f(row))
+    }
+
+    /// Return up to `limit` rows following the cursor, or from the start, if there is no cursor.
+    /// Fails if the cursor belongs to another table, panics if `limit` is zero, as such page could
+    /// never advance the cursor.
+    pub fn page(&self, after: Option<&Cursor>, limit: usize) -> Result<Page<T>, Error> {
+        assert!(limit > 0, "Page limit must be positive");
+        let mut items = vec![];
+        let mut last = None;
+        let mut next = None;
+
+        for (key, row) in self.rows(Cursor::key_in(after, T::NAME)?) {
+            if !self.matches(&row) {
+                continue;
+            }
+            if items.len() == limit {
+                next = last.map(|key| Cursor { source: T::NAME.to_string(), key });
+                break;
+            }
+            last = Some(key);
+            items.push(row);
+        }
+        Ok(Page { items, next })
+    }
+
+    /// Return all matching rows
+    pub fn fetch(&self) -> Vec<T> {
+        self.rows(None)
+            .map(|(_, row)| row)
+            .filter(|row| self.matches(row))
+            .collect()
+    }
+}
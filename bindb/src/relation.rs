@@ -0,0 +1,133 @@
+use crate::*;
+use crate::error::Result;
+use crate::watch::PendingChange;
+use heed::{BytesEncode, BytesDecode};
+
+/// Action taken with referencing rows, when the referenced row is deleted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnDelete {
+    /// Delete the referencing rows as well
+    Cascade,
+    /// Refuse to delete the referenced row
+    Restrict,
+}
+
+/// Reference from a row of `Self::Table` to the primary key of `Self::Parent`. It is an index,
+/// whose key starts with the key of the parent, so that all referencing rows can be found by a prefix scan.
+pub trait Reference: Index {
+    type Parent: Table;
+    const ON_DELETE: OnDelete;
+    /// Parent rows are owned by referencing rows. Parent is deleted, once its last owned reference is gone.
+    const OWNED: bool = false;
+
+    fn parent_key(t: &Self::Table) -> &<Self::Parent as Table>::Key;
+}
+
+pub trait References<T> {
+    fn on_register(db: Database) -> Database;
+    fn on_delete<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>, t: &T, changes: &mut Vec<Option<PendingChange>>) -> Result<()>;
+}
+
+#[impl_trait_for_tuples::impl_for_tuples(6)]
+#[tuple_types_no_default_trait_bound]
+impl<T> References<T> for Tuple
+    where T: Table,
+{
+    for_tuples!(where #(Tuple: Reference<Table=T> + 'static)*);
+
+    fn on_register(mut db: Database) -> Database {
+        for_tuples!( #(
+            db.relations.entry(Tuple::Parent::NAME).or_default().push(RelationOps::of::<Tuple>());
+        )* );
+        db
+    }
+
+    fn on_delete<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>, t: &T, changes: &mut Vec<Option<PendingChange>>) -> Result<()> {
+        for_tuples!( #(
+            if Tuple::OWNED {
+                let parent = Tuple::parent_key(t);
+                let encoded = KeyType::<Tuple::Parent>::bytes_encode(parent).map_err(Error::heed_codec)?;
+                if !is_owned(db, tx, Tuple::Parent::NAME, &encoded)? {
+                    delete::<Tuple::Parent>(db, tx, parent, changes)?;
+                }
+            }
+        )* );
+        Ok(())
+    }
+}
+
+type ChildrenFn = fn(&Database, &RoTxn, &[u8]) -> Result<Vec<Vec<u8>>>;
+type DeleteFn = for<'a> fn(&Database, &mut RwTxn<'a, 'a>, &[u8], &mut Vec<Option<PendingChange>>) -> Result<()>;
+
+/// Type-erased operations of a reference, registered under the name of the referenced table
+#[derive(Clone, Copy)]
+pub(crate) struct RelationOps {
+    child: &'static str,
+    on_delete: OnDelete,
+    owned: bool,
+    children: ChildrenFn,
+    delete_child: DeleteFn,
+}
+
+impl RelationOps {
+    fn of<R: Reference + 'static>() -> Self {
+        RelationOps {
+            child: R::Table::NAME,
+            on_delete: R::ON_DELETE,
+            owned: R::OWNED,
+            children: children::<R>,
+            delete_child: delete_encoded::<R::Table>,
+        }
+    }
+}
+
+/// Encoded primary keys of rows referencing encoded parent key
+fn children<R: Reference>(db: &Database, tx: &RoTxn, parent: &[u8]) -> Result<Vec<Vec<u8>>> {
+    db.raw_db(R::NAME)
+        .prefix_iter(tx, parent)?
+        .map(|r| r.map(|(_, child)| child.to_vec()).map_err(Error::from))
+        .collect()
+}
+
+fn delete_encoded<'a, T: Table>(db: &Database, tx: &mut RwTxn<'a, 'a>, key: &[u8], changes: &mut Vec<Option<PendingChange>>) -> Result<()> {
+    let key = KeyType::<T>::bytes_decode(key).map_err(Error::heed_codec)?;
+    delete::<T>(db, tx, &key, changes)
+}
+
+/// Whether any owned reference points to the encoded key of table `parent`
+fn is_owned(db: &Database, tx: &RoTxn, parent: &str, key: &[u8]) -> Result<bool> {
+    for rel in db.relations.get(parent).into_iter().flatten().filter(|r| r.owned) {
+        if !(rel.children)(db, tx, key)?.is_empty() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Delete a row, applying actions of all references pointing to it first
+pub(crate) fn delete<'a, T: Table>(db: &Database, tx: &mut RwTxn<'a, 'a>, k: &T::Key, changes: &mut Vec<Option<PendingChange>>) -> Result<()> {
+    let encoded = KeyType::<T>::bytes_encode(k).map_err(Error::heed_codec)?.into_owned();
+
+    for rel in db.relations.get(T::NAME).into_iter().flatten() {
+        let children = (rel.children)(db, tx, &encoded)?;
+        if children.is_empty() {
+            continue;
+        }
+        match rel.on_delete {
+            OnDelete::Restrict => return Err(Error::Restricted { table: T::NAME, referenced_by: rel.child }),
+            OnDelete::Cascade => for child in children {
+                (rel.delete_child)(db, tx, &child, changes)?;
+            }
+        }
+    }
+
+    changes.push(watch::capture::<T>(db, tx, k, None));
+    let typed = db.typed_db::<T>();
+    if let Some(item) = typed.get(tx, k)? {
+        // If the entry was stored, first update index table and only after that delete the entry
+        T::Indices::on_delete(db, tx, &item);
+        typed.delete(tx, k)?;
+        T::References::on_delete(db, tx, &item, changes)?;
+    }
+    Ok(())
+}
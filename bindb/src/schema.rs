@@ -0,0 +1,176 @@
+use crate::*;
+use crate::error::Result;
+use heed::BytesEncode;
+use heed::types::ByteSlice;
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+
+type Step = Box<dyn Fn(&[u8]) -> Result<Vec<u8>>>;
+
+/// Set of conversions, which bring rows stored by older versions of a table to `Table::VERSION`
+pub struct Migrations<T: Table> {
+    steps: BTreeMap<u32, Step>,
+    _p: PhantomData<T>,
+}
+
+impl<T: Table> Migrations<T> {
+    pub fn new() -> Self {
+        Migrations {
+            steps: BTreeMap::new(),
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<T: Table + 'static> Migrations<T> {
+    /// Add a migration, which converts rows stored with version `to - 1` of the table into version `to`.
+    /// `Old` and `New` are the row types as they were defined in these versions.
+    pub fn step<Old, New, F>(self, to: u32, f: F) -> Self
+        where Old: DeserializeOwned + 'static, New: Serialize + 'static, F: Fn(Old) -> New + 'static
+    {
+        self.step_from::<T::Codec, Old, New, F>(to, f)
+    }
+
+    /// Same as `step`, but the old rows are stored using codec `C`. Used when changing `Table::Codec`.
+    pub fn step_from<C, Old, New, F>(mut self, to: u32, f: F) -> Self
+        where C: Codec + 'static, Old: DeserializeOwned + 'static, New: Serialize + 'static, F: Fn(Old) -> New + 'static
+    {
+        self.steps.insert(to, Box::new(move |bytes| {
+            let old = C::decode::<Old>(bytes).map_err(Error::Codec)?;
+            T::Codec::encode(&f(old)).map_err(Error::Codec)
+        }));
+        self
+    }
+}
+
+impl<T: Table> Default for Migrations<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn version_key(name: &str) -> String {
+    format!("version.{}", name)
+}
+
+fn index_key(name: &str) -> String {
+    format!("index.{}", name)
+}
+
+/// Whether the index was filled from the rows of its table. Indices are built when first registered,
+/// so an empty index without rows to index is not built again on every start.
+pub(crate) fn is_built(db: &Database, tx: &RoTxn, name: &str) -> bool {
+    db.meta_db::<bool>().get(tx, &index_key(name)).unwrap().unwrap_or(false)
+}
+
+pub(crate) fn set_built<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>, name: &str) {
+    db.meta_db::<bool>().put(tx, &index_key(name), &true).unwrap();
+}
+
+/// Verify that a table of a read-only database is stored at the current version, as it can't be migrated
+pub(crate) fn check<T: Table>(db: &Database) -> Result<()> {
+    let tx = db.tx();
+    let stored = db.meta_db::<u32>().get(&tx.tx, &version_key(T::NAME))?.unwrap_or(0);
+    tx.commit();
+
+    if stored > T::VERSION {
+        return Err(Error::SchemaTooNew { table: T::NAME, stored, current: T::VERSION });
+    }
+    if stored < T::VERSION {
+        return Err(Error::NotMigrated { table: T::NAME, stored, current: T::VERSION });
+    }
+    Ok(())
+}
+
+/// Rows of a table being migrated are moved here while their keys are re-derived, so that they are never
+/// written to the table which is still being read
+const SCRATCH: &str = "__bindb_migrate";
+
+/// Call `f` with every entry of a raw database in key order. Entries are copied one at a time, so that `f`
+/// can write to the transaction while the database is being read.
+fn for_each<'a>(
+    db: heed::Database<ByteSlice, ByteSlice>, tx: &mut RwTxn<'a, 'a>,
+    mut f: impl FnMut(&mut RwTxn<'a, 'a>, Vec<u8>) -> Result<()>,
+) -> Result<()> {
+    let mut next = db.first(tx)?.map(|(k, v)| (k.to_vec(), v.to_vec()));
+    while let Some((k, v)) = next {
+        f(tx, v)?;
+        next = db.get_greater_than(tx, &k)?.map(|(k, v)| (k.to_vec(), v.to_vec()));
+    }
+    Ok(())
+}
+
+/// Compare stored schema version of a table with the current one, and run the migrations if necessary.
+/// All rows are rewritten, and the indices are rebuilt in a single write transaction, one row at a time.
+/// Indices which were added to a table with existing rows, and are not yet recorded as built, are built
+/// here as well. Databases written before these records existed have all their indices rebuilt once.
+/// Tables which are current do not take the write transaction at all.
+pub(crate) fn migrate<T: Table>(db: &Database) -> Result<()> {
+    let meta = db.meta_db::<u32>();
+    let tx = db.tx();
+    let current = meta.get(&tx.tx, &version_key(T::NAME))? == Some(T::VERSION)
+        && T::Indices::unbuilt(db, &tx.tx).is_empty();
+    tx.commit();
+    if current {
+        return Ok(());
+    }
+
+    let mut wtx = db.wtx();
+    let raw = db.raw_db(T::NAME);
+
+    let stored = match meta.get(&wtx.tx, &version_key(T::NAME))? {
+        Some(v) => v,
+        // Tables written before versioning was introduced are considered to be at version 0
+        None if !raw.is_empty(&wtx.tx)? => 0,
+        None => T::VERSION,
+    };
+
+    if stored > T::VERSION {
+        return Err(Error::SchemaTooNew { table: T::NAME, stored, current: T::VERSION });
+    }
+
+    if stored < T::VERSION {
+        let migrations = T::migrations();
+        let steps = (stored + 1..=T::VERSION).map(|v| {
+            migrations.steps.get(&v).ok_or(Error::MissingMigration { table: T::NAME, version: v })
+        }).collect::<Result<Vec<_>>>()?;
+
+        // Migration might have changed the primary key as well, rows are staged under their new keys
+        let scratch = db.tree.create_database_with_txn::<ByteSlice, ByteSlice>(Some(SCRATCH), &mut wtx.tx)?;
+        scratch.clear(&mut wtx.tx)?;
+        for_each(raw, &mut wtx.tx, |tx, mut v| {
+            for step in &steps {
+                v = step(&v)?;
+            }
+            let item = T::Codec::decode::<T>(&v).map_err(Error::Codec)?;
+            let k = KeyType::<T>::bytes_encode(T::get(&item)).map_err(Error::heed_codec)?;
+            Ok(scratch.put(tx, &k, &v)?)
+        })?;
+
+        raw.clear(&mut wtx.tx)?;
+        T::Indices::on_clear(db, &mut wtx.tx);
+        for_each(scratch, &mut wtx.tx, |tx, v| {
+            let item = T::Codec::decode::<T>(&v).map_err(Error::Codec)?;
+            let k = KeyType::<T>::bytes_encode(T::get(&item)).map_err(Error::heed_codec)?;
+            raw.append(tx, &k, &v)?;
+            T::Indices::on_insert(db, tx, &item);
+            Ok(())
+        })?;
+        scratch.clear(&mut wtx.tx)?;
+    } else {
+        let unbuilt = T::Indices::unbuilt(db, &wtx.tx);
+        if !unbuilt.is_empty() && !raw.is_empty(&wtx.tx)? {
+            T::Indices::clear_some(db, &mut wtx.tx, &unbuilt);
+            for_each(raw, &mut wtx.tx, |tx, v| {
+                let item = T::Codec::decode::<T>(&v).map_err(Error::Codec)?;
+                T::Indices::insert_into(db, tx, &item, &unbuilt);
+                Ok(())
+            })?;
+        }
+    }
+
+    T::Indices::mark_built(db, &mut wtx.tx);
+    meta.put(&mut wtx.tx, &version_key(T::NAME), &T::VERSION)?;
+    wtx.tx.commit()?;
+    Ok(())
+}
@@ -0,0 +1,165 @@
+use crate::*;
+use crate::error::Result;
+
+/// Sizes of a database, and usage of the B-tree of each of its tables and indices.
+///
+/// Everything is read from `mdb_env_info` and `mdb_stat`, so collecting statistics does not read any rows.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Stats {
+    /// Each registered table
+    pub tables: BTreeMap<String, TreeStats>,
+    /// Each registered index, including references
+    pub indices: BTreeMap<String, TreeStats>,
+    /// Size of a database page in bytes
+    pub page_size: u64,
+    /// Size of the memory map of the environment, which is the upper bound for the size of the data
+    pub map_size: u64,
+    /// Bytes of pages up to the last one used by a committed transaction, including free pages
+    pub used_size: u64,
+    /// Bytes currently occupied by the database file
+    pub file_size: u64,
+}
+
+/// Usage of the B-tree storing a table or an index, from `mdb_stat`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TreeStats {
+    pub entries: u64,
+    pub depth: u32,
+    pub branch_pages: u64,
+    pub leaf_pages: u64,
+    pub overflow_pages: u64,
+    /// Bytes of all pages of the tree
+    pub bytes: u64,
+}
+
+impl Database {
+    /// Names of all registered tables and indices, in no particular order
+    pub fn names(&self) -> impl Iterator<Item=&str> {
+        self.dbs.keys().map(String::as_str).filter(|n| *n != META)
+    }
+
+    /// Number of entries in a registered table or index, from `mdb_stat`
+    pub(crate) fn entries(&self, tx: &RoTxn, name: &str) -> Result<u64> {
+        Ok(self.raw_db(name).len(tx)?)
+    }
+
+    pub fn stats(&self) -> Result<Stats> {
+        // heed 0.11 only exposes the entry count of `mdb_stat`, the rest is read through a second handle of the
+        // environment. Our read transaction keeps pages of the current snapshot from being reused meanwhile.
+        let tx = self.tx();
+        let env = raw::Env::open(&self.path)?;
+        let info = env.info()?;
+        let rtx = env.read_txn()?;
+        let main = rtx.stat(None)?;
+
+        let mut stats = Stats {
+            page_size: main.ms_psize as u64,
+            map_size: info.me_mapsize as u64,
+            used_size: (info.me_last_pgno as u64 + 1) * main.ms_psize as u64,
+            file_size: std::fs::metadata(&self.path)?.len(),
+            ..Default::default()
+        };
+        for name in self.names() {
+            let stat = rtx.stat(Some(name))?;
+            let tree = TreeStats {
+                entries: stat.ms_entries as u64,
+                depth: stat.ms_depth,
+                branch_pages: stat.ms_branch_pages as u64,
+                leaf_pages: stat.ms_leaf_pages as u64,
+                overflow_pages: stat.ms_overflow_pages as u64,
+                bytes: (stat.ms_branch_pages + stat.ms_leaf_pages + stat.ms_overflow_pages) as u64 * stat.ms_psize as u64,
+            };
+            if self.tables.contains_key(name) {
+                stats.tables.insert(name.to_string(), tree);
+            } else {
+                stats.indices.insert(name.to_string(), tree);
+            }
+        }
+        drop(rtx);
+        tx.commit();
+        Ok(stats)
+    }
+}
+
+/// Read-only handle of an environment, which is already open through heed
+mod raw {
+    use lmdb_sys as ffi;
+    use std::ffi::CString;
+    use std::path::Path;
+    use std::ptr;
+
+    fn check(code: i32) -> Result<(), heed::Error> {
+        match code {
+            0 => Ok(()),
+            code => Err(heed::Error::Mdb(heed::MdbError::from_err_code(code))),
+        }
+    }
+
+    fn c_str(s: &str) -> Result<CString, heed::Error> {
+        CString::new(s).map_err(|e| heed::Error::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, e)))
+    }
+
+    pub struct Env(*mut ffi::MDB_env);
+
+    impl Env {
+        /// Opening the same environment twice in a process is only safe without the lock file, closing
+        /// this handle would otherwise release locks held by heed. Readers of this handle are not
+        /// registered, so callers need to hold a read transaction of the heed environment.
+        pub fn open(path: &Path) -> Result<Self, heed::Error> {
+            let path = c_str(&path.to_string_lossy())?;
+            let mut env = ptr::null_mut();
+            unsafe {
+                check(ffi::mdb_env_create(&mut env))?;
+                let env = Env(env);
+                check(ffi::mdb_env_set_maxdbs(env.0, 256))?;
+                let flags = ffi::MDB_NOSUBDIR | ffi::MDB_RDONLY | ffi::MDB_NOLOCK;
+                check(ffi::mdb_env_open(env.0, path.as_ptr(), flags, 0o444))?;
+                Ok(env)
+            }
+        }
+
+        pub fn info(&self) -> Result<ffi::MDB_envinfo, heed::Error> {
+            let mut info = std::mem::MaybeUninit::uninit();
+            unsafe {
+                check(ffi::mdb_env_info(self.0, info.as_mut_ptr()))?;
+                Ok(info.assume_init())
+            }
+        }
+
+        pub fn read_txn(&self) -> Result<Txn<'_>, heed::Error> {
+            let mut txn = ptr::null_mut();
+            unsafe {
+                check(ffi::mdb_txn_begin(self.0, ptr::null_mut(), ffi::MDB_RDONLY, &mut txn))?;
+            }
+            Ok(Txn(txn, std::marker::PhantomData))
+        }
+    }
+
+    impl Drop for Env {
+        fn drop(&mut self) {
+            unsafe { ffi::mdb_env_close(self.0) }
+        }
+    }
+
+    pub struct Txn<'e>(*mut ffi::MDB_txn, std::marker::PhantomData<&'e Env>);
+
+    impl<'e> Txn<'e> {
+        /// Statistics of a named database, or of the main database
+        pub fn stat(&self, name: Option<&str>) -> Result<ffi::MDB_stat, heed::Error> {
+            let name = name.map(c_str).transpose()?;
+            let mut dbi = 0;
+            let mut stat = std::mem::MaybeUninit::uninit();
+            unsafe {
+                check(ffi::mdb_dbi_open(self.0, name.as_ref().map_or(ptr::null(), |n| n.as_ptr()), 0, &mut dbi))?;
+                check(ffi::mdb_stat(self.0, dbi, stat.as_mut_ptr()))?;
+                Ok(stat.assume_init())
+            }
+        }
+    }
+
+    impl<'e> Drop for Txn<'e> {
+        fn drop(&mut self) {
+            unsafe { ffi::mdb_txn_abort(self.0) }
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use crate::*;
+use heed::BytesEncode;
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+
+/// Inverted index from words in text fields of a table to rows containing them. Add it to `Table::Indices`
+/// wrapped in `FullText`, and search it with `ROps::search`.
+pub trait TextIndex {
+    type Table: Table;
+    /// Name of the index. This should be unique within database
+    const NAME: &'static str;
+
+    /// Collect indexed text of a row
+    fn text<'a>(t: &'a Self::Table, out: &mut Vec<&'a str>);
+}
+
+/// Field types, which can be indexed by a `TextIndex`
+pub trait TextSource {
+    fn collect_text<'a>(&'a self, out: &mut Vec<&'a str>);
+}
+
+impl TextSource for str {
+    fn collect_text<'a>(&'a self, out: &mut Vec<&'a str>) {
+        out.push(self)
+    }
+}
+
+impl TextSource for String {
+    fn collect_text<'a>(&'a self, out: &mut Vec<&'a str>) {
+        out.push(self)
+    }
+}
+
+impl<T: TextSource> TextSource for Option<T> {
+    fn collect_text<'a>(&'a self, out: &mut Vec<&'a str>) {
+        if let Some(t) = self {
+            t.collect_text(out)
+        }
+    }
+}
+
+impl<T: TextSource> TextSource for Vec<T> {
+    fn collect_text<'a>(&'a self, out: &mut Vec<&'a str>) {
+        for t in self {
+            t.collect_text(out)
+        }
+    }
+}
+
+/// Maintains a `TextIndex` as a member of `Table::Indices`
+pub struct FullText<X>(PhantomData<X>);
+
+/// Lowercase alphanumeric words of the text, which are at least 2 characters long
+fn tokenize(text: &str) -> impl Iterator<Item=String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().nth(1).is_some())
+        .map(str::to_lowercase)
+}
+
+/// Number of occurrences of each word in the indexed text of a row
+fn terms<X: TextIndex>(t: &X::Table) -> BTreeMap<String, u32> {
+    let mut text = vec![];
+    X::text(t, &mut text);
+
+    let mut terms = BTreeMap::new();
+    for word in text.into_iter().flat_map(tokenize) {
+        *terms.entry(word).or_insert(0) += 1;
+    }
+    terms
+}
+
+/// Longest key LMDB accepts with its default build options
+const MAX_KEY_SIZE: usize = 511;
+
+/// Whether the entry key of the word fits into LMDB. Longer words, such as hashes or encoded blobs,
+/// are not indexed.
+fn fits(term: &str, pkey: &[u8]) -> bool {
+    term.len() + 1 + pkey.len() <= MAX_KEY_SIZE
+}
+
+/// Index entries are keyed by the word, followed by a zero byte and the encoded primary key,
+/// so that words can be looked up by prefix. Values hold the number of occurrences.
+fn entry_key(term: &str, pkey: &[u8]) -> Vec<u8> {
+    let mut key = Vec::with_capacity(term.len() + 1 + pkey.len());
+    key.extend_from_slice(term.as_bytes());
+    key.push(0);
+    key.extend_from_slice(pkey);
+    key
+}
+
+impl<X: TextIndex> IndexOps<X::Table> for FullText<X> {
+    fn on_register(db: Database) -> Database {
+        db.create_db(X::NAME)
+    }
+
+    fn on_insert<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>, t: &X::Table) {
+        let pkey = KeyType::<X::Table>::bytes_encode(<X::Table as Table>::get(t)).unwrap();
+        let raw = db.raw_db(X::NAME);
+        for (term, count) in terms::<X>(t).into_iter().filter(|(term, _)| fits(term, &pkey)) {
+            raw.put(tx, &entry_key(&term, &pkey), &count.to_be_bytes()).unwrap();
+        }
+    }
+
+    fn on_delete<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>, t: &X::Table) {
+        let pkey = KeyType::<X::Table>::bytes_encode(<X::Table as Table>::get(t)).unwrap();
+        let raw = db.raw_db(X::NAME);
+        for term in terms::<X>(t).keys().filter(|term| fits(term, &pkey)) {
+            raw.delete(tx, &entry_key(term, &pkey)).unwrap();
+        }
+    }
+
+    fn on_clear<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>) {
+        db.raw_db(X::NAME).clear(tx).unwrap();
+    }
+
+    fn db_name() -> &'static str {
+        X::NAME
+    }
+}
+
+/// Encoded primary keys of rows containing all words of the query, with their scores, best matches first.
+///
+/// Every query word matches all indexed words starting with it. Score of a row is the sum of tf-idf
+/// weights of the matched words, so rare words and repeated occurrences rank higher.
+pub(crate) fn search<X: TextIndex>(db: &Database, tx: &RoTxn, query: &str) -> Vec<(f64, Vec<u8>)> {
+    // Row count comes from `mdb_stat`, it does not depend on the size of the table
+    let rows = db.entries(tx, <X::Table as Table>::NAME).unwrap().max(1) as f64;
+    let raw = db.raw_db(X::NAME);
+
+    let mut words = tokenize(query).collect::<Vec<_>>();
+    words.sort();
+    words.dedup();
+
+    let mut scores: Option<BTreeMap<Vec<u8>, f64>> = None;
+    for word in words {
+        // Entries of a single word are adjacent, group them to compute its document frequency
+        let mut by_term = BTreeMap::<&[u8], Vec<(&[u8], u32)>>::new();
+        for entry in raw.prefix_iter(tx, word.as_bytes()).unwrap() {
+            let (key, val) = entry.unwrap();
+            let split = key.iter().position(|b| *b == 0).unwrap();
+            let mut count = [0; 4];
+            count.copy_from_slice(val);
+            by_term.entry(&key[..split]).or_default().push((&key[split + 1..], u32::from_be_bytes(count)));
+        }
+
+        let mut matched = BTreeMap::<Vec<u8>, f64>::new();
+        for postings in by_term.values() {
+            let idf = (1.0 + rows / postings.len() as f64).ln();
+            for (pkey, count) in postings {
+                *matched.entry(pkey.to_vec()).or_default() += (1.0 + (*count as f64).ln()) * idf;
+            }
+        }
+
+        scores = Some(match scores {
+            None => matched,
+            Some(prev) => prev.into_iter()
+                .filter_map(|(pkey, score)| matched.get(&pkey).map(|s| (pkey, score + s)))
+                .collect(),
+        });
+    }
+
+    let mut res = scores.unwrap_or_default().into_iter().map(|(pkey, score)| (score, pkey)).collect::<Vec<_>>();
+    // Stable sort keeps rows with equal score in primary key order
+    res.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+    res
+}
@@ -0,0 +1,118 @@
+use crate::*;
+use heed::BytesEncode;
+use serde::Deserialize;
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Key in the metadata database under which `ChangeInfo` is stored
+const CHANGES: &str = "changes";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// Mutation of a single row, delivered to listeners after the transaction was committed
+#[derive(Debug)]
+pub struct Change<T> {
+    /// Sequence number of the transaction, which made this change
+    pub seq: u64,
+    pub old: Option<T>,
+    pub new: Option<T>,
+}
+
+impl<T> Change<T> {
+    pub fn kind(&self) -> ChangeKind {
+        match (&self.old, &self.new) {
+            (None, _) => ChangeKind::Insert,
+            (Some(_), Some(_)) => ChangeKind::Update,
+            (Some(_), None) => ChangeKind::Delete,
+        }
+    }
+}
+
+/// Persisted counter of committed write transactions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeInfo {
+    /// Incremented by every write transaction, which modified some data
+    pub seq: u64,
+    /// Time of the last modification, in seconds since unix epoch
+    pub modified: u64,
+}
+
+/// Serialized row values captured during a write transaction, decoded only when delivered to a listener
+#[doc(hidden)]
+pub struct PendingChange {
+    table: &'static str,
+    old: Option<Vec<u8>>,
+    new: Option<Vec<u8>>,
+}
+
+type Listener = Arc<dyn Fn(u64, Option<&[u8]>, Option<&[u8]>) + Send + Sync>;
+
+#[derive(Clone, Default)]
+pub(crate) struct Listeners(Arc<RwLock<HashMap<&'static str, Vec<Listener>>>>);
+
+/// Capture the state of a row before it is written, if anyone listens for changes in its table
+pub(crate) fn capture<T: Table>(db: &Database, tx: &RoTxn, key: &T::Key, new: Option<&T>) -> Option<PendingChange> {
+    if !db.is_watched(T::NAME) {
+        return None;
+    }
+    let key = KeyType::<T>::bytes_encode(key).unwrap();
+    let old = db.raw_db(T::NAME).get(tx, &key).unwrap().map(<[u8]>::to_vec);
+    let new = new.map(|v| T::Codec::encode(v).unwrap());
+
+    if old.is_none() && new.is_none() {
+        return None;
+    }
+    Some(PendingChange { table: T::NAME, old, new })
+}
+
+/// Increment change sequence number, called just before the transaction is committed
+pub(crate) fn bump_seq<'a>(db: &Database, tx: &mut RwTxn<'a, 'a>) -> u64 {
+    let meta = db.meta_db::<ChangeInfo>();
+    let mut info = meta.get(tx, CHANGES).unwrap().unwrap_or_default();
+    info.seq += 1;
+    info.modified = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    meta.put(tx, CHANGES, &info).unwrap();
+    info.seq
+}
+
+impl Database {
+    /// Register a listener for mutations of table `T`. Listeners are called after the write transaction
+    /// which made the changes was successfully committed, on the thread which committed it.
+    ///
+    /// Only changes made through this process are delivered, other processes can poll `Database::changes`.
+    pub fn subscribe<T, F>(&self, f: F)
+        where T: Table + 'static, F: Fn(&Change<T>) + Send + Sync + 'static
+    {
+        let listener: Listener = Arc::new(move |seq, old, new| {
+            let decode = |bytes: Option<&[u8]>| bytes.map(|b| T::Codec::decode::<T>(b).unwrap());
+            f(&Change { seq, old: decode(old), new: decode(new) })
+        });
+        self.listeners.0.write().unwrap().entry(T::NAME).or_default().push(listener);
+    }
+
+    /// Sequence number and time of the last committed modification
+    pub fn changes(&self) -> ChangeInfo {
+        self.in_tx(|tx| {
+            self.meta_db::<ChangeInfo>().get(&tx.tx, CHANGES).unwrap().unwrap_or_default()
+        })
+    }
+
+    pub(crate) fn is_watched(&self, name: &str) -> bool {
+        self.listeners.0.read().unwrap().get(name).map(|l| !l.is_empty()).unwrap_or(false)
+    }
+
+    pub(crate) fn notify(&self, seq: u64, changes: Vec<PendingChange>) {
+        for change in changes {
+            // Don't hold the lock while calling listeners, they might want to subscribe
+            let listeners = self.listeners.0.read().unwrap().get(change.table).cloned().unwrap_or_default();
+            for listener in listeners {
+                listener(seq, change.old.as_deref(), change.new.as_deref());
+            }
+        }
+    }
+}
@@ -0,0 +1,23 @@
+#![feature(generic_associated_types)]
+mod common;
+
+use bindb::{Database, ROps};
+use common::*;
+
+#[test]
+fn test_batch() {
+    let db = Database::temporary().register::<Row>();
+
+    let mut batch = db.batch().max_ops(3);
+    // Keys are out of order at the end, so append needs to fall back to regular put
+    for id in (0..10).chain(vec![5, 2]) {
+        batch.append(&Row::new(id));
+    }
+    batch.delete::<Row>(&9);
+    batch.commit();
+
+    db.in_tx(|tx| {
+        assert_eq!(tx.scan::<Row>().count(), 9);
+        assert_eq!(tx.get_by::<RowNameIdx>(&"row-5".to_string()), Some(Row::new(5)));
+    });
+}
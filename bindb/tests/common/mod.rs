@@ -0,0 +1,53 @@
+//! Tables shared by the integration tests
+#![allow(dead_code)]
+
+use bindb::Table;
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Table)]
+pub struct Row {
+    #[bindb(key)]
+    pub id: u64,
+    #[bindb(index, unique)]
+    pub name: String,
+}
+
+impl Row {
+    pub fn new(id: u64) -> Self {
+        Row { id, name: format!("row-{}", id) }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Table)]
+pub struct Pkg {
+    #[bindb(key)]
+    pub id: usize,
+    #[bindb(index)]
+    pub name: String,
+    #[bindb(index)]
+    pub arch: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Table)]
+pub struct Group {
+    #[bindb(key)]
+    pub id: usize,
+}
+
+/// Owned by its group, the group is removed together with its last member
+#[derive(Debug, Deserialize, Serialize, PartialEq, Table)]
+pub struct Member {
+    #[bindb(key)]
+    pub id: usize,
+    #[bindb(references(table = "Group", on_delete = "cascade", owned))]
+    pub group: usize,
+}
+
+/// Prevents its member from being deleted
+#[derive(Debug, Deserialize, Serialize, PartialEq, Table)]
+pub struct Lock {
+    #[bindb(key)]
+    pub id: usize,
+    #[bindb(references(table = "Member", on_delete = "restrict"))]
+    pub member: usize,
+}
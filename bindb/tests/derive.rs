@@ -0,0 +1,30 @@
+#![feature(generic_associated_types)]
+
+use bindb::{Database, Table, Index, ROps, RwOps};
+use serde::{Serialize, Deserialize};
+
+#[test]
+fn test_derive() {
+    #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Table)]
+    #[bindb(name = "Derived")]
+    struct Item {
+        #[bindb(key)]
+        id: usize,
+        #[bindb(index)]
+        group: String,
+        #[bindb(index = "ItemByName", unique)]
+        name: String,
+    }
+
+    assert_eq!(<Item as Table>::NAME, "Derived");
+    assert_eq!(ItemGroupIdx::NAME, "DerivedGroupIdx_group_id");
+    assert_eq!(ItemByName::NAME, "ItemByName_name");
+
+    let db = Database::temporary().register::<Item>();
+    let mut tx = db.wtx();
+    let item = Item { id: 1, group: "a".to_string(), name: "first".to_string() };
+    tx.put(&item);
+
+    assert_eq!(tx.get_by::<ItemByName>(&"first".to_string()), Some(item.clone()));
+    assert_eq!(tx.get_by::<ItemGroupIdx>((&"a".to_string(), &1)), Some(item));
+}
@@ -0,0 +1,78 @@
+#![feature(generic_associated_types)]
+mod common;
+
+use bindb::{Database, ROps, RwOps};
+use common::*;
+
+#[test]
+fn test_dump_restore() {
+    let src = Database::temporary().register::<Row>();
+    src.in_wtx(|tx| {
+        tx.put(&Row::new(1));
+        tx.put(&Row::new(2));
+    });
+
+    let mut out = vec![];
+    src.dump(&mut out).unwrap();
+    assert_eq!(out.iter().filter(|c| **c == b'\n').count(), 2);
+
+    let dst = Database::temporary().register::<Row>();
+    dst.restore(&out[..]).unwrap();
+    dst.in_tx(|tx| {
+        assert_eq!(tx.scan::<Row>().count(), 2);
+        assert_eq!(tx.get_by::<RowNameIdx>(&"row-2".to_string()), Some(Row::new(2)));
+    });
+}
+
+#[test]
+fn test_restore_sequences() {
+    let src = Database::temporary().register::<Row>();
+    src.in_wtx(|tx| for _ in 0..3 {
+        let id = tx.next_id::<Row>().0;
+        tx.put(&Row::new(id));
+    });
+
+    let mut out = vec![];
+    src.dump(&mut out).unwrap();
+
+    let dst = Database::temporary().register::<Row>();
+    dst.restore(&out[..]).unwrap();
+    // Restored rows keep their keys, new ones are allocated after them
+    let next = dst.in_wtx(|tx| tx.next_id::<Row>());
+    assert_eq!(next.0, 4);
+    dst.in_tx(|tx| assert_eq!(tx.count::<Row>(), 3));
+}
+
+#[test]
+fn test_open_read_only() {
+    let missing = std::env::temp_dir().join("bindb-missing.mdb");
+    assert!(Database::open_read_only(&missing).is_err());
+    assert!(!missing.exists());
+
+    let src = Database::temporary().register::<Row>();
+    src.in_wtx(|tx| tx.put(&Row::new(1)));
+
+    // Tables which are not in the file are skipped instead of created
+    let db = Database::open_read_only(src.path()).unwrap().register::<Row>().register::<Group>();
+    let mut out = vec![];
+    db.dump(&mut out).unwrap();
+    assert_eq!(out.iter().filter(|c| **c == b'\n').count(), 1);
+    db.in_tx(|tx| assert_eq!(tx.get::<Row>(&1), Some(Row::new(1))));
+}
+
+#[test]
+fn test_restore_non_empty() {
+    let src = Database::temporary().register::<Row>();
+    src.in_wtx(|tx| tx.put(&Row::new(1)));
+    let mut out = vec![];
+    src.dump(&mut out).unwrap();
+
+    // Restored row would take over the name index entry of the existing one
+    let dst = Database::temporary().register::<Row>();
+    dst.in_wtx(|tx| tx.put(&Row { id: 2, ..Row::new(1) }));
+    assert!(matches!(dst.restore(&out[..]), Err(bindb::Error::NotEmpty(t)) if t == "Row"));
+    dst.in_tx(|tx| {
+        assert_eq!(tx.count::<Row>(), 1);
+        assert_eq!(tx.get_by::<RowNameIdx>(&"row-1".to_string()).map(|r| r.id), Some(2));
+    });
+}
@@ -0,0 +1,74 @@
+#![feature(generic_associated_types)]
+
+use bindb::{Database, Table, ROps, RwOps};
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Table)]
+struct Etag {
+    #[bindb(key)]
+    url: String,
+    etag: String,
+    #[bindb(expires)]
+    expires: Option<u64>,
+}
+
+#[test]
+fn test_expiry() {
+    let db = Database::temporary().register::<Etag>();
+    db.in_wtx(|tx| {
+        for i in 0..10 {
+            tx.put(&Etag { url: format!("https://a/{}", i), etag: i.to_string(), expires: Some(1000 + i) });
+        }
+        tx.put(&Etag { url: "https://b".into(), etag: "b".into(), expires: None });
+    });
+
+    assert_eq!(db.sweep(999, 3).unwrap(), 0);
+    assert_eq!(db.sweep(1004, 3).unwrap(), 5);
+    db.in_tx(|tx| {
+        assert_eq!(tx.count::<Etag>(), 6);
+        assert!(tx.get::<Etag>(&"https://a/4".to_string()).is_none());
+        assert!(tx.get::<Etag>(&"https://a/5".to_string()).is_some());
+    });
+
+    // Extending the expiry time moves the row in the index
+    db.in_wtx(|tx| tx.put(&Etag { url: "https://a/5".into(), etag: "5".into(), expires: Some(5000) }));
+    assert_eq!(db.sweep(u64::MAX - 1, 100).unwrap(), 5);
+    db.in_tx(|tx| assert_eq!(tx.count::<Etag>(), 1));
+}
+
+#[test]
+fn test_expiry_restricted() {
+    #[derive(Debug, Deserialize, Serialize, PartialEq, Table)]
+    struct Session {
+        #[bindb(key)]
+        id: u64,
+        #[bindb(expires)]
+        expires: u64,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq, Table)]
+    struct Pin {
+        #[bindb(key)]
+        id: u64,
+        #[bindb(references(table = "Session", on_delete = "restrict"))]
+        session: u64,
+    }
+
+    let db = Database::temporary().register::<Session>().register::<Pin>();
+    db.in_wtx(|tx| {
+        for id in 0..6 {
+            tx.put(&Session { id, expires: 100 + id });
+        }
+        tx.put(&Pin { id: 1, session: 0 });
+        tx.put(&Pin { id: 2, session: 3 });
+    });
+
+    // Pinned sessions are kept, and do not stop the sweep from deleting the ones after them
+    assert_eq!(db.sweep(1000, 2).unwrap(), 4);
+    let ids = db.in_tx(|tx| tx.scan::<Session>().map(|s| s.id).collect::<Vec<_>>());
+    assert_eq!(ids, vec![0, 3]);
+
+    db.in_wtx(|tx| tx.delete::<Pin>(&2));
+    assert_eq!(db.sweep(1000, 2).unwrap(), 1);
+    db.in_tx(|tx| assert_eq!(tx.count::<Session>(), 1));
+}
@@ -0,0 +1,30 @@
+#![feature(generic_associated_types)]
+
+use bindb::{Database, Table, Id, ROps, RwOps};
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Table)]
+struct Seq {
+    #[bindb(key)]
+    id: Id,
+}
+
+#[test]
+fn test_sequence() {
+    let db = Database::temporary().register::<Seq>();
+
+    let mut tx = db.wtx();
+    assert_eq!(tx.next_id::<Seq>(), Id(1));
+    drop(tx);
+
+    for _ in 0..300 {
+        db.in_wtx(|tx| {
+            let id = tx.next_id::<Seq>();
+            tx.put(&Seq { id });
+        });
+    }
+
+    // Keys are stored big-endian, so the scan returns them in allocation order
+    let ids = db.in_tx(|tx| tx.scan::<Seq>().map(|r| r.id.0).collect::<Vec<_>>());
+    assert_eq!(ids, (1..=300).collect::<Vec<_>>());
+}
@@ -0,0 +1,155 @@
+#![feature(generic_associated_types)]
+mod common;
+
+use bindb::{Database, Cursor, Error, Id, ROps, RwOps, Table};
+use common::*;
+use serde::{Serialize, Deserialize};
+
+/// Entries of a log per source, keyed by the source and a sequence
+#[derive(Debug, Deserialize, Serialize, PartialEq, Table)]
+struct Event {
+    #[bindb(key)]
+    id: (u32, Id),
+}
+
+#[test]
+fn test_query() {
+    let db = Database::temporary().register::<Pkg>();
+    let mut tx = db.wtx();
+    for id in 0..100 {
+        let name = if id % 3 == 0 { "kernel" } else { "openssl" };
+        let arch = if id % 2 == 0 { "x86_64" } else { "noarch" };
+        tx.put(&Pkg { id, name: name.to_string(), arch: arch.to_string() });
+    }
+
+    let query = tx.query::<Pkg>()
+        .by::<PkgNameIdx, _>(&"kernel")
+        .by::<PkgArchIdx, _>(&"x86_64")
+        .filter(|p| p.id > 10);
+    assert_eq!(query.fetch().len(), 15);
+
+    let first = query.page(None, 10).unwrap();
+    assert_eq!(first.items.first().map(|p| p.id), Some(12));
+    assert_eq!(first.items.len(), 10);
+
+    // Cursor survives a round trip through its token
+    let token = first.next.unwrap().to_string();
+    let second = query.page(Some(&token.parse().unwrap()), 10).unwrap();
+    assert_eq!(second.items.len(), 5);
+    assert!(second.next.is_none());
+
+    let either = tx.query::<Pkg>()
+        .by::<PkgArchIdx, _>(&"noarch")
+        .or_by::<PkgNameIdx, _>(&"kernel")
+        .fetch();
+    assert_eq!(either.len(), 50 + 17);
+}
+
+#[test]
+fn test_scan_from() {
+    let db = Database::temporary().register::<Row>();
+    db.in_wtx(|tx| for id in 0..25 {
+        tx.put(&Row { id, name: format!("{}", 100 - id) });
+    });
+
+    // Page through the table, each page in its own transaction
+    let mut ids = vec![];
+    let mut cursor = Some(Cursor::after::<Row>(&4));
+    while let Some(after) = cursor {
+        let page = db.in_tx(|tx| tx.scan_from::<Row>(Some(&after), 10).unwrap());
+        ids.extend(page.items.iter().map(|r| r.id));
+        cursor = page.next;
+    }
+    assert_eq!(ids, (5..25).collect::<Vec<_>>());
+
+    // Names sort in reverse order of ids
+    let page = db.in_tx(|tx| tx.scan_index_from::<RowNameIdx>(Some(&Cursor::after_index::<RowNameIdx>(&"80".to_string())), 3).unwrap());
+    assert_eq!(page.items.iter().map(|r| r.id).collect::<Vec<_>>(), vec![19, 18, 17]);
+    assert!(page.next.is_some());
+}
+
+#[test]
+fn test_query_restrictions() {
+    let db = Database::temporary().register::<Pkg>();
+    let mut tx = db.wtx();
+    for id in 0..30 {
+        let name = ["kernel", "kernel-devel", "openssl"][id % 3];
+        let arch = if id % 2 == 0 { "x86_64" } else { "noarch" };
+        tx.put(&Pkg { id, name: name.to_string(), arch: arch.to_string() });
+    }
+
+    // Strings are compared whole, a prefix of a name matches nothing
+    assert_eq!(tx.query::<Pkg>().by::<PkgNameIdx, _>(&"kern").fetch().len(), 0);
+    assert_eq!(tx.query::<Pkg>().by::<PkgNameIdx, _>(&"kernel").fetch().len(), 10);
+
+    // Alternatives are united with everything restricted before them
+    let ids = tx.query::<Pkg>()
+        .by::<PkgNameIdx, _>(&"kernel")
+        .by::<PkgArchIdx, _>(&"x86_64")
+        .or_by::<PkgNameIdx, _>(&"openssl")
+        .keys(0..12)
+        .fetch()
+        .into_iter()
+        .map(|p| p.id)
+        .collect::<Vec<_>>();
+    assert_eq!(ids, vec![0, 2, 5, 6, 8, 11]);
+
+    // Pages are enumerated from the keys, index restrictions are checked on the rows
+    let query = tx.query::<Pkg>().keys(10..30).by::<PkgArchIdx, _>(&"noarch");
+    let mut ids = vec![];
+    let mut cursor = None;
+    loop {
+        let page = query.page(cursor.as_ref(), 4).unwrap();
+        ids.extend(page.items.iter().map(|p| p.id));
+        match page.next {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+    assert_eq!(ids, (10..30).filter(|id| id % 2 == 1).collect::<Vec<_>>());
+}
+
+#[test]
+#[should_panic(expected = "Page limit must be positive")]
+fn test_empty_page() {
+    let db = Database::temporary().register::<Pkg>();
+    db.in_tx(|tx| tx.query::<Pkg>().page(None, 0).unwrap());
+}
+
+#[test]
+fn test_cursor_source() {
+    let db = Database::temporary().register::<Row>().register::<Pkg>();
+    db.in_wtx(|tx| for id in 0..5 {
+        tx.put(&Row::new(id));
+    });
+
+    let next = db.in_tx(|tx| tx.scan_from::<Row>(None, 2).unwrap().next.unwrap());
+    let token = next.to_string();
+    assert_eq!(token.parse::<Cursor>().unwrap(), next);
+
+    // Cursors are only accepted by the table or index they were created for
+    db.in_tx(|tx| {
+        assert!(matches!(tx.scan_from::<Pkg>(Some(&next), 2), Err(Error::InvalidCursor(_))));
+        assert!(matches!(tx.scan_index_from::<RowNameIdx>(Some(&next), 2), Err(Error::InvalidCursor(_))));
+        assert!(matches!(tx.query::<Pkg>().page(Some(&next), 2), Err(Error::InvalidCursor(_))));
+        assert_eq!(tx.query::<Row>().page(Some(&next), 2).unwrap().items, vec![Row::new(2), Row::new(3)]);
+    });
+    assert!(matches!("0102".parse::<Cursor>(), Err(Error::InvalidCursor(_))));
+}
+
+#[test]
+fn test_last_with_prefix() {
+    let db = Database::temporary().register::<Event>();
+    db.in_wtx(|tx| for source in 1..4 {
+        for seq in 1..300 {
+            tx.put(&Event { id: (source, Id(seq)) });
+        }
+    });
+
+    // Sequences are compared as big-endian bytes, so the highest one is the last key of its source
+    db.in_tx(|tx| {
+        assert_eq!(tx.last_with_prefix::<Event, _>(&2u32), Some(Event { id: (2, Id(299)) }));
+        assert_eq!(tx.last_with_prefix::<Event, _>(&3u32), Some(Event { id: (3, Id(299)) }));
+        assert_eq!(tx.last_with_prefix::<Event, _>(&4u32), None);
+    });
+}
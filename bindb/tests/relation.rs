@@ -0,0 +1,70 @@
+#![feature(generic_associated_types)]
+mod common;
+
+use bindb::{Database, Table, Index, Error, ROps, RwOps};
+use serde::{Serialize, Deserialize};
+use common::*;
+
+#[test]
+fn test_references() {
+    let db = Database::temporary()
+        .register::<Group>()
+        .register::<Member>()
+        .register::<Lock>();
+
+    let mut tx = db.wtx();
+    tx.put(&Group { id: 1 });
+    tx.put(&Group { id: 2 });
+    tx.put(&Member { id: 1, group: 1 });
+    tx.put(&Member { id: 2, group: 1 });
+    tx.put(&Member { id: 3, group: 2 });
+    tx.put(&Lock { id: 1, member: 3 });
+
+    // Deleting a group deletes its members
+    tx.delete::<Group>(&1);
+    assert_eq!(tx.get::<Member>(&1), None);
+    assert_eq!(tx.get::<Member>(&2), None);
+
+    // Locked member can't be deleted
+    assert!(matches!(tx.try_delete::<Member>(&3), Err(Error::Restricted { table: "Member", referenced_by: "Lock" })));
+    assert_eq!(tx.get::<Member>(&3), Some(Member { id: 3, group: 2 }));
+
+    // Group is deleted together with its last member
+    tx.delete::<Lock>(&1);
+    tx.delete::<Member>(&3);
+    assert_eq!(tx.get::<Group>(&2), None);
+    tx.commit();
+}
+
+#[test]
+fn test_named_references() {
+    #[derive(Debug, Deserialize, Serialize, PartialEq, Table)]
+    struct Transfer {
+        #[bindb(key)]
+        id: usize,
+        #[bindb(references(table = "Group", on_delete = "cascade"))]
+        from: usize,
+        #[bindb(references(table = "Group", name = "TransferTargetRef", on_delete = "restrict"))]
+        to: usize,
+    }
+
+    assert_ne!(TransferGroupRef::NAME, TransferTargetRef::NAME);
+
+    let db = Database::temporary().register::<Group>().register::<Transfer>();
+    let mut tx = db.wtx();
+    for id in 1..=3 {
+        tx.put(&Group { id });
+    }
+    tx.put(&Transfer { id: 1, from: 1, to: 2 });
+    tx.put(&Transfer { id: 2, from: 3, to: 1 });
+
+    assert_eq!(tx.referencing::<TransferGroupRef>(&1), vec![1]);
+    assert_eq!(tx.referencing::<TransferTargetRef>(&1), vec![2]);
+
+    // Group 1 is a target of a transfer, and can't be deleted until the transfer is gone
+    assert!(matches!(tx.try_delete::<Group>(&1), Err(Error::Restricted { table: "Group", referenced_by: "Transfer" })));
+    tx.delete::<Transfer>(&2);
+    tx.delete::<Group>(&1);
+    assert_eq!(tx.get::<Transfer>(&1), None);
+    assert_eq!(tx.referencing::<TransferTargetRef>(&2), Vec::<usize>::new());
+}
@@ -0,0 +1,144 @@
+#![feature(generic_associated_types)]
+
+use bindb::{Database, Table, Migrations, Error, ROps, RwOps};
+use serde::{Serialize, Deserialize};
+
+#[test]
+fn test_migrate() {
+    #[derive(Debug, Deserialize, Serialize, Table)]
+    #[bindb(name = "Versioned")]
+    struct V0 {
+        #[bindb(key)]
+        id: usize,
+        name: String,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq, Table)]
+    #[bindb(name = "Versioned", version = 1, migrations = "v1_migrations")]
+    struct V1 {
+        #[bindb(key)]
+        id: usize,
+        #[bindb(index, unique)]
+        name: String,
+        len: usize,
+    }
+
+    fn v1_migrations() -> Migrations<V1> {
+        Migrations::new().step(1, |old: V0| V1 { id: old.id, len: old.name.len(), name: old.name })
+    }
+
+    let v0 = Database::temporary().register::<V0>();
+    v0.in_wtx(|tx| tx.put(&V0 { id: 1, name: "abc".to_string() }));
+
+    let db = Database::open(v0.path()).register::<V1>();
+    let expected = V1 { id: 1, name: "abc".to_string(), len: 3 };
+    db.in_tx(|tx| {
+        assert_eq!(tx.get::<V1>(&1).as_ref(), Some(&expected));
+        assert_eq!(tx.get_by::<V1NameIdx>(&"abc".to_string()).as_ref(), Some(&expected));
+    });
+
+    match Database::open(v0.path()).try_register::<V0>() {
+        Err(Error::SchemaTooNew { stored: 1, current: 0, .. }) => {}
+        _ => panic!("Registering older schema should fail"),
+    }
+}
+
+#[test]
+fn test_added_index() {
+    #[derive(Debug, Deserialize, Serialize, Table)]
+    #[bindb(name = "Indexed")]
+    struct Plain {
+        #[bindb(key)]
+        id: usize,
+        name: String,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq, Table)]
+    #[bindb(name = "Indexed")]
+    struct Indexed {
+        #[bindb(key)]
+        id: usize,
+        #[bindb(index, unique)]
+        name: String,
+    }
+
+    let plain = Database::temporary().register::<Plain>();
+    plain.in_wtx(|tx| tx.put(&Plain { id: 1, name: "abc".to_string() }));
+
+    // Index added without a version bump is built from the existing rows
+    let db = Database::open(plain.path()).register::<Indexed>();
+    let expected = Indexed { id: 1, name: "abc".to_string() };
+    db.in_tx(|tx| assert_eq!(tx.get_by::<IndexedNameIdx>(&"abc".to_string()), Some(expected)));
+}
+
+#[test]
+fn test_error_send_sync() {
+    #[derive(Debug, Deserialize, Serialize, Table)]
+    #[bindb(name = "Broken")]
+    struct Text {
+        #[bindb(key)]
+        id: usize,
+        value: String,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Table)]
+    #[bindb(name = "Broken", version = 1, migrations = "number_migrations")]
+    struct Number {
+        #[bindb(key)]
+        id: usize,
+        value: u64,
+    }
+
+    fn number_migrations() -> Migrations<Number> {
+        Migrations::new().step(1, |n: Number| n)
+    }
+
+    // Errors go through `?` into boxed errors, such as anyhow's, with their source kept
+    fn boxed(e: Error) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+        e.into()
+    }
+
+    let db = Database::temporary().register::<Text>();
+    db.in_wtx(|tx| tx.put(&Text { id: 1, value: "one".to_string() }));
+    let err = Database::open(db.path()).try_register::<Number>().map(|_| ()).unwrap_err();
+    assert!(matches!(err, Error::Codec(_)));
+    let err = boxed(err);
+    assert!(err.to_string().starts_with("Serialization error"));
+    assert!(err.source().is_some());
+}
+
+#[test]
+fn test_migrate_key() {
+    #[derive(Debug, Deserialize, Serialize, Table)]
+    #[bindb(name = "Rekeyed")]
+    struct Old {
+        #[bindb(key)]
+        id: u32,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq, Table)]
+    #[bindb(name = "Rekeyed", version = 1, migrations = "rekey_migrations")]
+    struct New {
+        #[bindb(key)]
+        id: u32,
+        #[bindb(index)]
+        old: u32,
+    }
+
+    fn rekey_migrations() -> Migrations<New> {
+        Migrations::new().step(1, |o: Old| New { id: o.id + 1, old: o.id })
+    }
+
+    let old = Database::temporary().register::<Old>();
+    old.in_wtx(|tx| for id in 0..300 {
+        tx.put(&Old { id });
+    });
+
+    // Every row is migrated exactly once, even though it moves to a key which is yet to be read
+    let db = Database::open(old.path()).register::<New>();
+    db.in_tx(|tx| {
+        assert_eq!(tx.count::<New>(), 300);
+        assert!(tx.scan::<New>().all(|n| n.id == n.old + 1));
+        assert_eq!(tx.query::<New>().by::<NewOldIdx, _>(&299u32).fetch(), vec![New { id: 300, old: 299 }]);
+    });
+}
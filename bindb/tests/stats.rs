@@ -0,0 +1,41 @@
+#![feature(generic_associated_types)]
+mod common;
+
+use bindb::{Database, Index, RwOps};
+use common::*;
+
+#[test]
+fn test_stats() {
+    let db = Database::temporary().register::<Pkg>();
+    db.in_wtx(|tx| for id in 0..10 {
+        tx.put(&Pkg { id, name: format!("pkg-{}", id % 2), arch: "noarch".to_string() });
+    });
+
+    let stats = db.stats().unwrap();
+    let pkg = stats.tables["Pkg"];
+    assert_eq!(pkg.entries, 10);
+    assert_eq!(stats.indices[PkgNameIdx::NAME].entries, 10);
+    assert_eq!((pkg.depth, pkg.leaf_pages, pkg.branch_pages), (1, 1, 0));
+    assert_eq!(pkg.bytes, stats.page_size);
+    // Map size is the one the environment was opened with, not the file size
+    assert_eq!(stats.map_size, 1 << 40);
+    assert!(stats.used_size > 0 && stats.used_size <= stats.file_size);
+    assert!(stats.file_size <= stats.map_size);
+
+    let mut names = db.names().collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, vec!["Pkg", PkgArchIdx::NAME, PkgNameIdx::NAME]);
+}
+
+#[test]
+fn test_stats_depth() {
+    let db = Database::temporary().register::<Row>();
+    assert_eq!(db.stats().unwrap().tables["Row"], Default::default());
+
+    db.in_wtx(|tx| for id in 0..10_000 {
+        tx.put(&Row::new(id));
+    });
+    let row = db.stats().unwrap().tables["Row"];
+    assert_eq!(row.entries, 10_000);
+    assert!(row.depth >= 2 && row.branch_pages > 0 && row.leaf_pages > row.branch_pages);
+}
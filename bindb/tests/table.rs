@@ -0,0 +1,87 @@
+#![feature(generic_associated_types)]
+mod common;
+
+use bindb::{table, Database, Table, ROps, RwOps};
+use serde::{Serialize, Deserialize};
+use common::*;
+
+#[test]
+fn test_simple() {
+    #[derive(Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+    struct Item(usize, usize);
+    table!(Item => 0(usize));
+
+    let db = Database::temporary().register::<Item>();
+    {
+        let mut db = db.wtx();
+        db.put(&Item(0, 0));
+        db.put(&Item(1, 0));
+        db.put(&Item(2, 0));
+        db.put(&Item(4, 0));
+        db.put(&Item(0, 0));
+        db.commit();
+    }
+    let mut db = db.wtx();
+    assert_eq!(db.get(&2), Some(Item(2, 0)));
+
+    let range = db.scan::<Item>();
+    assert_eq!(range.count(), 4);
+
+    db.delete::<Item>(&0);
+    assert_eq!(db.scan::<Item>().count(), 3);
+}
+
+#[test]
+fn test_keys_and_entries() {
+    let db = Database::temporary().register::<Group>().register::<Member>();
+    let mut tx = db.wtx();
+    tx.put(&Group { id: 1 });
+    tx.put(&Group { id: 2 });
+    for id in 0..6 {
+        tx.put(&Member { id, group: 1 + id % 2 });
+    }
+
+    assert_eq!(tx.count::<Member>(), 6);
+    assert_eq!(tx.scan_keys::<Member>().collect::<Vec<_>>(), (0..6).collect::<Vec<_>>());
+
+    let entry = tx.scan_entries::<Member>().find(|e| e.key == 3).unwrap();
+    assert_eq!(entry.value(), Member { id: 3, group: 2 });
+
+    assert_eq!(tx.referencing::<MemberGroupRef>(&2), vec![1, 3, 5]);
+}
+
+#[test]
+fn test_temporary() {
+    let db = Database::temporary().register::<Row>();
+    let other = Database::temporary().register::<Row>();
+    assert_ne!(db.path(), other.path());
+
+    db.in_wtx(|tx| tx.put(&Row::new(1)));
+    other.in_tx(|tx| assert_eq!(tx.get::<Row>(&1), None));
+
+    // File is removed once the last clone is dropped
+    let path = db.path().to_path_buf();
+    let clone = db.clone();
+    drop(db);
+    assert!(path.exists());
+    clone.in_tx(|tx| assert_eq!(tx.get::<Row>(&1), Some(Row::new(1))));
+    drop(clone);
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_overwrite_indices() {
+    let db = Database::temporary().register::<Row>().register::<Pkg>();
+    let mut tx = db.wtx();
+    tx.put(&Row::new(1));
+    tx.put(&Row { id: 1, name: "renamed".to_string() });
+    tx.put(&Pkg { id: 1, name: "bash".to_string(), arch: "x86_64".to_string() });
+    tx.put(&Pkg { id: 1, name: "bash".to_string(), arch: "noarch".to_string() });
+
+    // Overwritten row is no longer reachable through its old index keys
+    assert_eq!(tx.get_by::<RowNameIdx>(&"row-1".to_string()), None);
+    assert_eq!(tx.get_by::<RowNameIdx>(&"renamed".to_string()).map(|r| r.id), Some(1));
+    assert!(tx.query::<Pkg>().by::<PkgArchIdx, _>(&"x86_64").fetch().is_empty());
+    assert_eq!(tx.query::<Pkg>().by::<PkgArchIdx, _>(&"noarch").fetch().len(), 1);
+    tx.commit();
+}
@@ -0,0 +1,50 @@
+#![feature(generic_associated_types)]
+
+use bindb::{Database, Table, ROps, RwOps};
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Table)]
+struct Doc {
+    #[bindb(key)]
+    id: usize,
+    #[bindb(text)]
+    title: String,
+    #[bindb(text)]
+    body: Option<String>,
+}
+
+#[test]
+fn test_search() {
+    let db = Database::temporary().register::<Doc>();
+    let mut tx = db.wtx();
+    tx.put(&Doc { id: 1, title: "openssl security update".into(), body: Some("Fixes heap overflow in OpenSSL".into()) });
+    tx.put(&Doc { id: 2, title: "kernel security update".into(), body: Some("Heap overflow, heap corruption".into()) });
+    tx.put(&Doc { id: 3, title: "openssl bug fix".into(), body: None });
+
+    let ids = |docs: Vec<Doc>| docs.into_iter().map(|d| d.id).collect::<Vec<_>>();
+    assert_eq!(ids(tx.search::<DocText>("OpenSSL heap overflow", 10)), vec![1]);
+    assert_eq!(ids(tx.search::<DocText>("open", 10)), vec![1, 3]);
+    // Repeated words rank higher
+    assert_eq!(ids(tx.search::<DocText>("heap", 10)), vec![2, 1]);
+
+    // Overwritten rows are no longer found by their old text
+    tx.put(&Doc { id: 3, title: "glibc bug fix".into(), body: None });
+    assert_eq!(ids(tx.search::<DocText>("openssl", 10)), vec![1]);
+
+    assert_eq!(ids(tx.query::<Doc>().matching::<DocText>("security").filter(|d| d.id > 1).fetch()), vec![2]);
+}
+
+#[test]
+fn test_long_words() {
+    let db = Database::temporary().register::<Doc>();
+    let blob = "a".repeat(600);
+    let mut tx = db.wtx();
+    tx.put(&Doc { id: 1, title: format!("signature {}", blob), body: None });
+
+    // Words longer than a key can hold are not indexed, the rest of the text is
+    assert_eq!(tx.search::<DocText>("signature", 10).len(), 1);
+    assert_eq!(tx.search::<DocText>(&blob, 10).len(), 0);
+
+    tx.delete::<Doc>(&1);
+    assert_eq!(tx.search::<DocText>("signature", 10).len(), 0);
+}
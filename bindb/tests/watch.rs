@@ -0,0 +1,37 @@
+#![feature(generic_associated_types)]
+mod common;
+
+use bindb::{Database, ChangeKind, RwOps};
+use std::sync::{Arc, Mutex};
+use common::*;
+
+#[test]
+fn test_watch() {
+    let db = Database::temporary().register::<Row>();
+
+    let seen = Arc::new(Mutex::new(vec![]));
+    let sink = seen.clone();
+    db.subscribe::<Row, _>(move |c| sink.lock().unwrap().push((c.seq, c.kind())));
+
+    db.in_wtx(|tx| {
+        tx.put(&Row::new(1));
+        tx.put(&Row::new(2));
+    });
+    // Nothing is delivered for aborted transactions, or those which did not modify anything
+    let mut tx = db.wtx();
+    tx.put(&Row::new(3));
+    drop(tx);
+    db.in_wtx(|_| {});
+    db.in_wtx(|tx| {
+        tx.put(&Row { id: 1, name: "renamed".to_string() });
+        tx.delete::<Row>(&2);
+    });
+
+    assert_eq!(*seen.lock().unwrap(), vec![
+        (1, ChangeKind::Insert),
+        (1, ChangeKind::Insert),
+        (2, ChangeKind::Update),
+        (2, ChangeKind::Delete),
+    ]);
+    assert_eq!(db.changes().seq, 2);
+}
@@ -0,0 +1,93 @@
+//! Bulk load of packages with their details, the way a first sync of a large repository writes them.
+//! Compares a single write transaction with `Batch` chunked commits, and with its sorted-append fast path.
+//! Run with `cargo bench -p cache --bench batch`.
+
+use bindb::{Batch, RwOps};
+use cache::*;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+const PKGS: usize = 100_000;
+/// Bulk loads of 100k packages are expected to take seconds
+const TARGET: Duration = Duration::from_secs(10);
+
+fn pkgs() -> Vec<(Pkg, PkgDetail)> {
+    (0..PKGS).map(|i| {
+        let pkg = Pkg {
+            id: Uuid::new_v4(),
+            nevra: Nevra {
+                name: format!("python3-package-{}", i % 5000),
+                epoch: 0,
+                ver: format!("{}.{}", i % 7, i % 13),
+                rel: format!("{}.el8", i / 5000),
+                arch: "x86_64".to_string(),
+            },
+        };
+        let detail = PkgDetail {
+            pkg_id: pkg.id,
+            summary: format!("Python package number {}", i),
+            description: "Library providing functionality for other python packages, built for RHEL 8. ".repeat(4),
+            checksum: Some(Checksum { r#type: "sha256".to_string(), value: format!("{:064x}", i) }),
+            packager: Some("Red Hat, Inc.".to_string()),
+            url: Some(format!("https://pypi.org/project/package-{}", i % 5000)),
+            build_time: 1_600_000_000 + i as u64,
+            file_time: 1_600_000_000 + i as u64,
+            package_size: 100_000,
+            installed_size: 400_000,
+            archive_size: 410_000,
+            source_rpm: Some(format!("python-package-{}-1.0-1.el8.src.rpm", i % 5000)),
+        };
+        (pkg, detail)
+    }).collect()
+}
+
+fn load(name: &str, pkgs: &[(Pkg, PkgDetail)], write: impl FnOnce(&bindb::Database, &[(Pkg, PkgDetail)])) {
+    let db = cache::temporary();
+    let start = Instant::now();
+    write(&db, pkgs);
+    let elapsed = start.elapsed();
+
+    let size = std::fs::metadata(db.path()).unwrap().len();
+    println!(
+        "{:>15}: {} packages in {:.2?} ({:.0} packages/s), {:.1} MiB{}",
+        name, pkgs.len(), elapsed, pkgs.len() as f64 / elapsed.as_secs_f64(), size as f64 / (1024.0 * 1024.0),
+        if elapsed > TARGET { ", slower than the target" } else { "" }
+    );
+}
+
+fn put_all(batch: &mut Batch, pkgs: &[(Pkg, PkgDetail)], append: bool) {
+    for (pkg, detail) in pkgs {
+        if append {
+            batch.append(pkg);
+            batch.append(detail);
+        } else {
+            batch.put(pkg);
+            batch.put(detail);
+        }
+    }
+}
+
+fn main() {
+    let mut pkgs = pkgs();
+    load("single", &pkgs, |db, pkgs| db.in_wtx(|tx| for (pkg, detail) in pkgs {
+        tx.put(pkg);
+        tx.put(detail);
+    }));
+    load("batch", &pkgs, |db, pkgs| {
+        let mut batch = db.batch();
+        put_all(&mut batch, pkgs, false);
+        batch.commit();
+    });
+    // Append only pays off when rows come ordered by their keys
+    load("unsorted append", &pkgs, |db, pkgs| {
+        let mut batch = db.batch();
+        put_all(&mut batch, pkgs, true);
+        batch.commit();
+    });
+    pkgs.sort_by_key(|(pkg, _)| pkg.id);
+    load("append", &pkgs, |db, pkgs| {
+        let mut batch = db.batch();
+        put_all(&mut batch, pkgs, true);
+        batch.commit();
+    });
+}
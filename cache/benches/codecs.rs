@@ -0,0 +1,81 @@
+use bindb::{Codec, Json, Bincode, Cbor, Zstd};
+use cache::*;
+use criterion::{criterion_group, criterion_main, Criterion, black_box};
+use serde::{Serialize, de::DeserializeOwned};
+use uuid::Uuid;
+
+fn pkgs() -> Vec<Pkg> {
+    (0..1000).map(|i| Pkg {
+        id: Uuid::new_v4(),
+        nevra: Nevra {
+            name: format!("kernel-modules-extra-{}", i),
+            epoch: 0,
+            ver: "4.18.0".to_string(),
+            rel: format!("305.{}.1.el8_4", i),
+            arch: "x86_64".to_string(),
+        },
+    }).collect()
+}
+
+fn advisories() -> Vec<Advisory> {
+    let desc = "The kernel packages contain the Linux kernel, the core of any Linux operating system.\n\n\
+        Security Fix(es):\n\n\
+        * kernel: use-after-free in show_numa_stats function (CVE-2019-20811)\n\
+        * kernel: out-of-bounds write in xdp_umem_reg in net/xdp/xdp_umem.c (CVE-2020-12659)\n\
+        * kernel: buffer overflow in mwifiex_cmd_802_11_ad_hoc_start function (CVE-2020-12654)\n\n\
+        For more details about the security issue(s), including the impact, a CVSS score, \
+        acknowledgments, and other related information, refer to the CVE page(s) listed in the References section.\n\n\
+        Bug Fix(es):\n\n\
+        * kernel-rt: update RT source tree to the RHEL-8.4.z source tree\n";
+
+    (0..1000).map(|i| Advisory {
+        id: Uuid::new_v4(),
+        r#type: "security".to_string(),
+        name: format!("RHSA-2021:{:04}", i),
+        title: "Important: kernel security and bug fix update".to_string(),
+        severity: Severity::Important,
+        status: "final".to_string(),
+        from: "release-engineering@redhat.com".to_string(),
+        summary: Some("Important: kernel security and bug fix update".to_string()),
+        desc: Some(desc.repeat(1 + i % 4)),
+        solution: None,
+        rights: Some("Copyright 2021 Red Hat Inc".to_string()),
+        release: Some("0".to_string()),
+        issued: parse_date("2021-05-18 00:00:00"),
+        updated: parse_date("2021-05-18 00:00:00"),
+    }).collect()
+}
+
+fn bench_codec<C: Codec, T: Serialize + DeserializeOwned>(c: &mut Criterion, table: &str, codec: &str, rows: &[T]) {
+    let encoded = rows.iter().map(|r| C::encode(r).unwrap()).collect::<Vec<_>>();
+    let size: usize = encoded.iter().map(Vec::len).sum();
+    println!("{}/{}: {} bytes for {} rows", table, codec, size, rows.len());
+
+    c.bench_function(&format!("{}/{}/encode", table, codec), |b| {
+        b.iter(|| for r in rows {
+            black_box(C::encode(r).unwrap());
+        })
+    });
+
+    c.bench_function(&format!("{}/{}/decode", table, codec), |b| {
+        b.iter(|| for e in &encoded {
+            black_box(C::decode::<T>(e).unwrap());
+        })
+    });
+}
+
+fn bench_table<T: Serialize + DeserializeOwned>(c: &mut Criterion, table: &str, rows: &[T]) {
+    bench_codec::<Json, T>(c, table, "json", rows);
+    bench_codec::<Bincode, T>(c, table, "bincode", rows);
+    bench_codec::<Cbor, T>(c, table, "cbor", rows);
+    bench_codec::<Zstd<Json>, T>(c, table, "zstd-json", rows);
+    bench_codec::<Zstd<Bincode>, T>(c, table, "zstd-bincode", rows);
+}
+
+fn codecs(c: &mut Criterion) {
+    bench_table(c, "pkg", &pkgs());
+    bench_table(c, "advisory", &advisories());
+}
+
+criterion_group!(benches, codecs);
+criterion_main!(benches);
@@ -0,0 +1,60 @@
+#![feature(generic_associated_types)]
+//! Compares inserting packages keyed by random UUIDs with keys allocated from a sequence.
+//! Run with `cargo bench -p cache --bench ids`.
+
+use bindb::{Database, Id, Table};
+use cache::Nevra;
+use serde::{Serialize, Deserialize};
+use std::time::Instant;
+use uuid::Uuid;
+
+const ROWS: u64 = 200_000;
+
+#[derive(Serialize, Deserialize, Table)]
+struct UuidPkg {
+    #[bindb(key)]
+    id: Uuid,
+    #[bindb(index = "UuidPkgNevra", unique)]
+    nevra: Nevra,
+}
+
+#[derive(Serialize, Deserialize, Table)]
+struct SeqPkg {
+    #[bindb(key)]
+    id: Id,
+    #[bindb(index = "SeqPkgNevra", unique)]
+    nevra: Nevra,
+}
+
+fn nevra(i: u64) -> Nevra {
+    Nevra {
+        name: format!("python3-package-{}", i % 5000),
+        epoch: 0,
+        ver: format!("{}.{}", i % 7, i % 13),
+        rel: format!("{}.el8", i / 5000),
+        arch: "x86_64".to_string(),
+    }
+}
+
+fn load<T: Table + 'static>(name: &str, mut row: impl FnMut(&mut bindb::Batch, u64) -> T) {
+    let db = Database::temporary().register::<T>();
+    let start = Instant::now();
+    let mut batch = db.batch().max_ops(10_000);
+    for i in 0..ROWS {
+        let r = row(&mut batch, i);
+        batch.put(&r);
+    }
+    batch.commit();
+    let elapsed = start.elapsed();
+
+    let size = std::fs::metadata(db.path()).unwrap().len();
+    println!(
+        "{:>5}: {} rows in {:.2?} ({:.0} rows/s), {:.1} MiB",
+        name, ROWS, elapsed, ROWS as f64 / elapsed.as_secs_f64(), size as f64 / (1024.0 * 1024.0)
+    );
+}
+
+fn main() {
+    load("uuid", |_, i| UuidPkg { id: Uuid::new_v4(), nevra: nevra(i) });
+    load("seq", |batch, i| SeqPkg { id: batch.next_id::<SeqPkg>(), nevra: nevra(i) });
+}
@@ -0,0 +1,25 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+
+const USAGE: &str = "Usage:
+    cachedb backup <db> <target> [--compact]  Copy the database into target file
+    cachedb dump <db> [target]                Write all tables as JSON lines, to stdout by default
+    cachedb restore <db> <source>             Load JSON lines written by dump";
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+
+    match args.as_slice() {
+        ["backup", db, target] => cache::open_read_only(db)?.backup_to(target, false)?,
+        ["backup", db, target, "--compact"] => cache::open_read_only(db)?.backup_to(target, true)?,
+        ["dump", db] => cache::open_read_only(db)?.dump(BufWriter::new(std::io::stdout()))?,
+        ["dump", db, target] => cache::open_read_only(db)?.dump(BufWriter::new(File::create(target)?))?,
+        ["restore", db, source] => cache::open(db).restore(BufReader::new(File::open(source)?))?,
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    }
+    Ok(())
+}
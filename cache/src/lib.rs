@@ -1,22 +1,160 @@
 #![feature(generic_associated_types)]
-use bindb::{index, table, Table, Index};
+use bindb::{Database, Table, Migrations, Json, ROps, Id};
 use serde::{Serialize, Deserialize};
 use uuid::Uuid;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use std::collections::BTreeMap;
+use std::path::Path;
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+/// Open the cache database and register all of its tables
+pub fn open(path: impl AsRef<Path>) -> Database {
+    register(Database::open(path))
+}
+
+/// Open an existing cache database without creating, migrating or otherwise modifying it
+pub fn open_read_only(path: impl AsRef<Path>) -> Result<Database, bindb::Error> {
+    Ok(register(Database::open_read_only(path)?))
+}
+
+/// Empty cache database in a temporary file, which is removed on drop
+pub fn temporary() -> Database {
+    register(Database::temporary())
+}
+
+fn register(db: Database) -> Database {
+    db.register::<Product>()
+        .register::<ContentSet>()
+        .register::<Repo>()
+        .register::<RepoSync>()
+        .register::<Pkg>()
+        .register::<PkgDetail>()
+        .register::<PkgLocation>()
+        .register::<SrcPkg>()
+        .register::<PkgSrc>()
+        .register::<Advisory>()
+        .register::<Module>()
+        .register::<ModuleStream>()
+        .register::<PkgAdvisory>()
+        .register::<PkgRepo>()
+        .register::<AdvisoryRepo>()
+        .register::<Cve>()
+        .register::<AdvisoryCve>()
+        .register::<Bugzilla>()
+        .register::<AdvisoryBugzilla>()
+        .register::<StreamPkg>()
+        .register::<StreamProfile>()
+        .register::<StreamDep>()
+        .register::<ModuleDefault>()
+}
+
+/// Product from the repolist
+#[derive(Debug, Clone, Deserialize, Serialize, Table)]
+pub struct Product {
+    #[bindb(key)]
+    pub id: Uuid,
+    #[bindb(index = "ProductNameIdx", unique)]
+    pub name: String,
+}
+
+/// Content set of a product. Its repositories are expansions of the content set urls
+/// for each basearch and releasever.
+#[derive(Debug, Clone, Deserialize, Serialize, Table)]
+pub struct ContentSet {
+    #[bindb(key)]
+    pub id: Uuid,
+    /// Unique across all products, the repolist loader rejects a label listed under two products
+    #[bindb(index = "ContentSetLabelIdx", unique)]
+    pub label: String,
+    pub name: String,
+    #[bindb(references(table = "Product", on_delete = "cascade"))]
+    pub product_id: Uuid,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Table)]
+#[bindb(version = 1, migrations = "repo_migrations")]
 pub struct Repo {
+    #[bindb(key)]
     pub id: Uuid,
+    // We make non-unique index by including the primary key
+    #[bindb(index = "RepoLabel")]
     pub label: String,
+    #[bindb(index = "RepoUrl", unique)]
     pub url: String,
     pub basearch: Option<String>,
     pub releasever: Option<String>,
     pub revision: Option<String>,
+    /// Nil for repos migrated from version 0, until the next repolist load links them or removes them
+    #[serde(default)]
+    #[bindb(references(table = "ContentSet", on_delete = "cascade"))]
+    pub content_set_id: Uuid,
 }
 
-table!(Repo => id(Uuid), RepoUrl, RepoLabel);
-// We make non-unique index by including the primary key
-index!(RepoLabel, Repo, label: String, id: Uuid);
-index!(RepoUrl, Repo, url: String);
+fn repo_migrations() -> Migrations<Repo> {
+    // Content sets of version 0 repos are unknown, rows get a nil `content_set_id`, which no content set has.
+    // The next repolist load either links them to their content sets, or removes them as not listed.
+    Migrations::new().step(1, |repo: Repo| repo)
+}
+
+/// Content sets of a product
+pub fn content_sets_of(tx: &impl ROps, product_id: &Uuid) -> Vec<ContentSet> {
+    tx.referencing::<ContentSetProductRef>(product_id)
+        .iter()
+        .filter_map(|id| tx.get::<ContentSet>(id))
+        .collect()
+}
+
+/// Repositories expanded from a content set
+pub fn repos_of(tx: &impl ROps, content_set_id: &Uuid) -> Vec<Repo> {
+    tx.referencing::<RepoContentSetRef>(content_set_id)
+        .iter()
+        .filter_map(|id| tx.get::<Repo>(id))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Deserialize, Serialize)]
+pub struct RepoSyncId {
+    pub repo_id: Uuid,
+    /// Allocated from a sequence when the attempt is stored, orders attempts of a repository by the time they finished
+    pub seq: Id,
+}
+
+/// Attempt to sync a repository. Attempts do not reference the repository, so that the history
+/// is kept after the repository is removed.
+#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize, Table)]
+pub struct RepoSync {
+    #[bindb(key)]
+    pub id: RepoSyncId,
+    pub url: String,
+    pub started: DateTime<Utc>,
+    pub finished: Option<DateTime<Utc>>,
+    pub old_revision: Option<String>,
+    /// Revision found in repomd, even if the sync failed
+    pub new_revision: Option<String>,
+    /// Checksum of primary.xml as listed in repomd, `type:value`. It describes the package list only,
+    /// repomd.xml itself is not checksummed.
+    pub primary_checksum: Option<String>,
+    pub pkgs_added: u64,
+    pub pkgs_removed: u64,
+    pub advs_added: u64,
+    pub advs_removed: u64,
+    pub error: Option<String>,
+}
+
+impl RepoSync {
+    pub fn succeeded(&self) -> bool {
+        self.finished.is_some() && self.error.is_none()
+    }
+}
+
+/// Sync attempts of a repository, oldest first
+pub fn syncs_of(tx: &impl ROps, repo_id: &Uuid) -> Vec<RepoSync> {
+    tx.query::<RepoSync>().key_prefix(repo_id).fetch()
+}
+
+/// Most recent sync attempt of a repository
+pub fn last_sync(tx: &impl ROps, repo_id: &Uuid) -> Option<RepoSync> {
+    tx.last_with_prefix::<RepoSync, _>(repo_id)
+}
 
 #[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize)]
 pub struct Nevra {
@@ -27,14 +165,131 @@ pub struct Nevra {
     pub arch: String,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+impl Nevra {
+    /// Parse `name-[epoch:]ver-rel.arch`, such as `nodejs-1:14.17.0-1.module+el8.4.0.x86_64`
+    pub fn parse(s: &str) -> Option<Nevra> {
+        let mut parts = s.rsplitn(2, '.');
+        let (arch, rest) = (parts.next()?, parts.next()?);
+        let mut parts = rest.rsplitn(3, '-');
+        let (rel, ver, name) = (parts.next()?, parts.next()?, parts.next()?);
+        let (epoch, ver) = match ver.find(':') {
+            Some(i) => (ver[..i].parse().ok()?, &ver[i + 1..]),
+            None => (0, ver),
+        };
+        Some(Nevra {
+            name: name.to_string(),
+            epoch,
+            ver: ver.to_string(),
+            rel: rel.to_string(),
+            arch: arch.to_string(),
+        })
+    }
+}
+
+// Packages and advisories are kept only as long as some repository contains them
+#[derive(Debug, Clone, Deserialize, Serialize, Table)]
+#[bindb(version = 1, migrations = "pkg_migrations")]
 pub struct Pkg {
+    #[bindb(key)]
     pub id: Uuid,
+    #[bindb(index = "PkgNevraIdx", unique)]
     pub nevra: Nevra,
 }
 
-table!(Pkg => nevra(Nevra), PkgNevraIdx);
-index!(PkgNevraIdx, Pkg, nevra: Nevra);
+fn pkg_migrations() -> Migrations<Pkg> {
+    // Version 0 was keyed by nevra, rows are re-keyed by id
+    Migrations::new().step(1, |pkg: Pkg| pkg)
+}
+
+#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize)]
+pub struct Checksum {
+    pub r#type: String,
+    pub value: String,
+}
+
+/// Package metadata from primary.xml, which does not depend on the repository containing the package
+#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize, Table)]
+#[bindb(codec = "bindb::Zstd<bindb::Bincode>")]
+pub struct PkgDetail {
+    #[bindb(key)]
+    #[bindb(references(table = "Pkg", on_delete = "cascade"))]
+    pub pkg_id: Uuid,
+    #[bindb(text = "PkgText")]
+    pub summary: String,
+    #[bindb(text = "PkgText")]
+    pub description: String,
+    pub checksum: Option<Checksum>,
+    pub packager: Option<String>,
+    pub url: Option<String>,
+    pub build_time: u64,
+    pub file_time: u64,
+    pub package_size: u64,
+    pub installed_size: u64,
+    pub archive_size: u64,
+    pub source_rpm: Option<String>,
+}
+
+/// Source package, from which binary packages were built
+#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize, Table)]
+pub struct SrcPkg {
+    #[bindb(key)]
+    pub id: Uuid,
+    /// File name of the source package, such as `openssl-3.0.7-1.el9.src.rpm`
+    #[bindb(index = "SrcPkgFilenameIdx", unique)]
+    pub filename: String,
+    /// Parsed from the file name, which does not contain the epoch
+    pub nevra: Option<Nevra>,
+}
+
+impl SrcPkg {
+    pub fn new(id: Uuid, filename: String) -> Self {
+        let nevra = parse_filename(&filename);
+        SrcPkg { id, filename, nevra }
+    }
+}
+
+/// Parse `name-ver-rel.arch.rpm`, epoch is always 0
+fn parse_filename(filename: &str) -> Option<Nevra> {
+    Nevra::parse(filename.strip_suffix(".rpm")?)
+}
+
+/// Link from a binary package to the source package it was built from
+#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize, Table)]
+pub struct PkgSrc {
+    #[bindb(key)]
+    #[bindb(references(table = "Pkg", on_delete = "cascade"))]
+    pub pkg_id: Uuid,
+    #[bindb(references(table = "SrcPkg", on_delete = "cascade", owned))]
+    pub src_id: Uuid,
+}
+
+/// Source package of a binary package
+pub fn source_of(tx: &impl ROps, pkg_id: &Uuid) -> Option<SrcPkg> {
+    let link = tx.get::<PkgSrc>(pkg_id)?;
+    tx.get::<SrcPkg>(&link.src_id)
+}
+
+/// Binary packages built from the source package with given file name
+pub fn binaries_of(tx: &impl ROps, filename: &str) -> Vec<Pkg> {
+    let src = match tx.get_by::<SrcPkgFilenameIdx>(&filename.to_string()) {
+        Some(src) => src,
+        None => return vec![],
+    };
+    tx.referencing::<PkgSrcSrcPkgRef>(&src.id)
+        .iter()
+        .filter_map(|id| tx.get::<Pkg>(id))
+        .collect()
+}
+
+/// Path of a package file relative to the repository, the same package can be stored under different paths
+#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize, Table)]
+pub struct PkgLocation {
+    #[bindb(key)]
+    #[bindb(references(table = "Pkg", field = "pkg_id", on_delete = "cascade"))]
+    #[bindb(references(table = "Repo", field = "repo_id", on_delete = "cascade"))]
+    pub id: PkgRepoId,
+    pub href: String,
+}
 
 #[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Deserialize, Serialize)]
 pub struct PkgRepoId {
@@ -42,23 +297,107 @@ pub struct PkgRepoId {
     pub repo_id: Uuid,
 }
 
-#[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Deserialize, Serialize)]
-pub struct PkgRepo(pub PkgRepoId);
-table!(PkgRepo => 0(PkgRepoId));
+#[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Deserialize, Serialize, Table)]
+pub struct PkgRepo(
+    #[bindb(key)]
+    #[bindb(references(table = "Pkg", field = "pkg_id", on_delete = "cascade", owned))]
+    #[bindb(references(table = "Repo", field = "repo_id", on_delete = "cascade"))]
+    pub PkgRepoId
+);
 
-#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize)]
+/// Severity of an advisory, ordered from the least severe
+#[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Severity {
+    None,
+    Low,
+    Moderate,
+    Important,
+    Critical,
+}
+
+impl Severity {
+    /// Parse severity from updateinfo, unknown values are treated as `None`
+    pub fn parse(s: Option<&str>) -> Self {
+        match s.map(|s| s.trim().to_ascii_lowercase()).as_deref() {
+            Some("low") => Severity::Low,
+            Some("moderate") => Severity::Moderate,
+            Some("important") => Severity::Important,
+            Some("critical") => Severity::Critical,
+            _ => Severity::None,
+        }
+    }
+}
+
+/// Parse a date from updateinfo, which is either `YYYY-MM-DD HH:MM:SS`, `YYYY-MM-DD`, or seconds since unix epoch
+pub fn parse_date(s: &str) -> Option<DateTime<Utc>> {
+    let s = s.trim();
+    if let Ok(secs) = s.parse::<i64>() {
+        return Utc.timestamp_opt(secs, 0).single();
+    }
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").ok()
+        .or_else(|| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()?.and_hms_opt(0, 0, 0))
+        .map(|d| Utc.from_utc_datetime(&d))
+}
+
+// Descriptions are large blocks of text, which compress well
+#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize, Table)]
+#[bindb(codec = "bindb::Zstd<bindb::Bincode>", version = 2, migrations = "advisory_migrations")]
 pub struct Advisory {
+    #[bindb(key)]
     pub id: Uuid,
     pub r#type: String,
+    #[bindb(index = "AdvisoryNameIdx", unique)]
     pub name: String,
+    #[bindb(text = "AdvisoryText")]
+    pub title: String,
+    #[bindb(index = "AdvisorySeverityIdx")]
+    pub severity: Severity,
+    pub status: String,
+    pub from: String,
+    #[bindb(text = "AdvisoryText")]
     pub summary: Option<String>,
+    #[bindb(text = "AdvisoryText")]
     pub desc: Option<String>,
-    pub issued: String,
-    pub updated: String,
+    pub solution: Option<String>,
+    pub rights: Option<String>,
+    pub release: Option<String>,
+    pub issued: Option<DateTime<Utc>>,
+    pub updated: Option<DateTime<Utc>>,
 }
 
-table!(Advisory => id(Uuid), AdvisoryNameIdx);
-index!(AdvisoryNameIdx, Advisory, name: String);
+/// Advisory as stored by versions 0 and 1
+#[derive(Deserialize, Serialize)]
+struct AdvisoryV1 {
+    id: Uuid,
+    r#type: String,
+    name: String,
+    summary: Option<String>,
+    desc: Option<String>,
+    issued: String,
+    updated: String,
+}
+
+fn advisory_migrations() -> Migrations<Advisory> {
+    Migrations::new()
+        .step_from::<Json, AdvisoryV1, AdvisoryV1, _>(1, |adv| adv)
+        // Fields missing in older versions are filled in by the next sync
+        .step(2, |adv: AdvisoryV1| Advisory {
+            id: adv.id,
+            r#type: adv.r#type,
+            title: adv.name.clone(),
+            name: adv.name,
+            severity: Severity::None,
+            status: String::new(),
+            from: String::new(),
+            summary: adv.summary,
+            desc: adv.desc,
+            solution: None,
+            rights: None,
+            release: None,
+            issued: parse_date(&adv.issued),
+            updated: parse_date(&adv.updated),
+        })
+}
 
 #[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Deserialize, Serialize)]
 pub struct AdvisoryRepoId {
@@ -66,20 +405,107 @@ pub struct AdvisoryRepoId {
     pub repo_id: Uuid,
 }
 
-#[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Deserialize, Serialize)]
-pub struct AdvisoryRepo(pub AdvisoryRepoId);
-table!(AdvisoryRepo => 0(AdvisoryRepoId));
+#[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Deserialize, Serialize, Table)]
+pub struct AdvisoryRepo(
+    #[bindb(key)]
+    #[bindb(references(table = "Advisory", field = "adv_id", on_delete = "cascade", owned))]
+    #[bindb(references(table = "Repo", field = "repo_id", on_delete = "cascade"))]
+    pub AdvisoryRepoId
+);
 
-#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialOrd, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct PkgAdvisoryId {
     pub pkg_id: Uuid,
     pub adv_id: Uuid,
+    /// Module stream the package is fixed in. Not a reference, reposcan deletes the link with the stream.
     pub stream_id: Option<Uuid>,
 }
 
+#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize, Table)]
+pub struct PkgAdvisory(
+    #[bindb(key)]
+    #[bindb(references(table = "Pkg", field = "pkg_id", on_delete = "cascade"))]
+    #[bindb(references(table = "Advisory", field = "adv_id", on_delete = "cascade"))]
+    pub PkgAdvisoryId
+);
+
+/// Vulnerability fixed by advisories, from `cve` references in updateinfo
+#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize, Table)]
+pub struct Cve {
+    /// Identifier, such as `CVE-2021-3449`
+    #[bindb(key)]
+    pub name: String,
+    pub href: Option<String>,
+}
+
 #[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize)]
-pub struct PkgAdvisory(pub PkgAdvisoryId);
-table!(PkgAdvisory => 0(PkgAdvisoryId));
+pub struct AdvisoryCveId {
+    pub adv_id: Uuid,
+    pub cve: String,
+}
+
+#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize, Table)]
+pub struct AdvisoryCve(
+    #[bindb(key)]
+    #[bindb(references(table = "Advisory", field = "adv_id", on_delete = "cascade"))]
+    #[bindb(references(table = "Cve", field = "cve", on_delete = "cascade", owned))]
+    pub AdvisoryCveId
+);
+
+/// Bug fixed by advisories, from `bugzilla` references in updateinfo
+#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize, Table)]
+pub struct Bugzilla {
+    #[bindb(key)]
+    pub id: String,
+    pub href: Option<String>,
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize)]
+pub struct AdvisoryBugzillaId {
+    pub adv_id: Uuid,
+    pub bug_id: String,
+}
+
+#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize, Table)]
+pub struct AdvisoryBugzilla(
+    #[bindb(key)]
+    #[bindb(references(table = "Advisory", field = "adv_id", on_delete = "cascade"))]
+    #[bindb(references(table = "Bugzilla", field = "bug_id", on_delete = "cascade", owned))]
+    pub AdvisoryBugzillaId
+);
+
+/// Advisories fixing a CVE
+pub fn advisories_fixing(tx: &impl ROps, cve: &str) -> Vec<Advisory> {
+    tx.referencing::<AdvisoryCveCveRef>(&cve.to_string())
+        .iter()
+        .filter_map(|link| tx.get::<Advisory>(&link.adv_id))
+        .collect()
+}
+
+/// CVEs fixed by an advisory with given name, such as `RHSA-2021:1024`
+pub fn cves_fixed_by(tx: &impl ROps, advisory: &str) -> Vec<Cve> {
+    let adv = match tx.get_by::<AdvisoryNameIdx>(&advisory.to_string()) {
+        Some(adv) => adv,
+        None => return vec![],
+    };
+    tx.referencing::<AdvisoryCveAdvisoryRef>(&adv.id)
+        .iter()
+        .filter_map(|link| tx.get::<Cve>(&link.cve))
+        .collect()
+}
+
+/// Bugs fixed by an advisory with given name
+pub fn bugs_fixed_by(tx: &impl ROps, advisory: &str) -> Vec<Bugzilla> {
+    let adv = match tx.get_by::<AdvisoryNameIdx>(&advisory.to_string()) {
+        Some(adv) => adv,
+        None => return vec![],
+    };
+    tx.referencing::<AdvisoryBugzillaAdvisoryRef>(&adv.id)
+        .iter()
+        .filter_map(|link| tx.get::<Bugzilla>(&link.bug_id))
+        .collect()
+}
 
 #[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize)]
 pub struct ModuleAttrs {
@@ -88,20 +514,39 @@ pub struct ModuleAttrs {
     pub arch: String,
 }
 
-#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize, Table)]
 pub struct Module {
+    #[bindb(key)]
     pub id: Uuid,
+    #[bindb(index = "ModuleAttrsIdx", unique)]
+    #[bindb(references(table = "Repo", field = "repo_id", on_delete = "cascade"))]
     pub attrs: ModuleAttrs,
 }
 
-table!(Module => id(Uuid), ModuleAttrsIdx);
-index!(ModuleAttrsIdx, Module, attrs: ModuleAttrs);
-
-#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize, Table)]
+#[bindb(version = 1, migrations = "stream_migrations")]
 pub struct ModuleStream {
+    #[bindb(key)]
     pub id: Uuid,
+    #[bindb(index = "StreamAttrsIdx", unique)]
+    #[bindb(references(table = "Module", field = "module_id", on_delete = "cascade"))]
     pub attrs: StreamAttrs,
     pub default: bool,
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Names of packages forming the public interface of the stream
+    #[serde(default)]
+    pub api: Vec<String>,
+    /// Names of source packages the stream is built from
+    #[serde(default)]
+    pub components: Vec<String>,
+}
+
+fn stream_migrations() -> Migrations<ModuleStream> {
+    // Fields added in version 1 have defaults
+    Migrations::new().step(1, |stream: ModuleStream| stream)
 }
 
 #[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize)]
@@ -112,5 +557,113 @@ pub struct StreamAttrs {
     pub context: String,
 }
 
-table!(ModuleStream => id(Uuid), StreamAttrsIdx);
-index!(StreamAttrsIdx, ModuleStream, attrs : StreamAttrs);
+#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize)]
+pub struct StreamPkgId {
+    pub nevra: Nevra,
+    pub stream_id: Uuid,
+}
+
+/// Package shipped by a module stream, from modulemd artifacts. Keyed by nevra first, so that streams
+/// containing a package are found by a key prefix, regardless of whether the package itself was synced.
+#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize, Table)]
+pub struct StreamPkg(
+    #[bindb(key)]
+    #[bindb(references(table = "ModuleStream", field = "stream_id", on_delete = "cascade"))]
+    pub StreamPkgId
+);
+
+#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize)]
+pub struct StreamProfileId {
+    pub stream_id: Uuid,
+    pub name: String,
+}
+
+/// Named set of packages of a module stream, which are installed together
+#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize, Table)]
+pub struct StreamProfile {
+    #[bindb(key)]
+    #[bindb(references(table = "ModuleStream", field = "stream_id", on_delete = "cascade"))]
+    pub id: StreamProfileId,
+    pub description: Option<String>,
+    /// Package names
+    pub rpms: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize)]
+pub struct StreamDepId {
+    pub stream_id: Uuid,
+    /// Name of the required module
+    pub module: String,
+    /// Dependency is only needed to build the stream
+    pub build: bool,
+}
+
+/// Dependency of a module stream on streams of another module
+#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize, Table)]
+pub struct StreamDep {
+    #[bindb(key)]
+    #[bindb(references(table = "ModuleStream", field = "stream_id", on_delete = "cascade"))]
+    pub id: StreamDepId,
+    /// Acceptable streams, empty if any stream satisfies the dependency
+    pub streams: Vec<String>,
+}
+
+/// Streams shipping a package, a package is modular if there is at least one
+pub fn streams_of(tx: &impl ROps, nevra: &Nevra) -> Vec<ModuleStream> {
+    tx.query::<StreamPkg>()
+        .key_prefix(nevra)
+        .fetch()
+        .iter()
+        .filter_map(|link| tx.get::<ModuleStream>(&link.0.stream_id))
+        .collect()
+}
+
+/// Packages shipped by a module stream
+pub fn packages_of(tx: &impl ROps, stream_id: &Uuid) -> Vec<Nevra> {
+    tx.referencing::<StreamPkgModuleStreamRef>(stream_id)
+        .into_iter()
+        .map(|id| id.nevra)
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize)]
+pub struct ModuleDefaultId {
+    pub repo_id: Uuid,
+    /// Name of the module
+    pub module: String,
+}
+
+/// Default stream and profiles of a module in a repository, from modulemd-defaults
+#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize, Table)]
+pub struct ModuleDefault {
+    #[bindb(key)]
+    #[bindb(references(table = "Repo", field = "repo_id", on_delete = "cascade"))]
+    pub id: ModuleDefaultId,
+    pub stream: Option<String>,
+    /// Names of default profiles of each stream
+    pub profiles: BTreeMap<String, Vec<String>>,
+    /// Defaults overriding the above on systems with given intent, such as `server` or `desktop`
+    pub intents: BTreeMap<String, ModuleIntent>,
+}
+
+#[derive(Debug, Clone, PartialOrd, PartialEq, Deserialize, Serialize)]
+pub struct ModuleIntent {
+    pub stream: Option<String>,
+    pub profiles: BTreeMap<String, Vec<String>>,
+}
+
+impl ModuleDefault {
+    pub fn is_default(&self, stream: &str) -> bool {
+        self.stream.as_deref() == Some(stream)
+    }
+
+    /// Default profiles of a stream
+    pub fn profiles_of(&self, stream: &str) -> &[String] {
+        self.profiles.get(stream).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// Defaults of a module in a repository, if it has any
+pub fn default_of(tx: &impl ROps, repo_id: Uuid, module: &str) -> Option<ModuleDefault> {
+    tx.get::<ModuleDefault>(&ModuleDefaultId { repo_id, module: module.to_string() })
+}
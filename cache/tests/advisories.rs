@@ -0,0 +1,102 @@
+#![feature(generic_associated_types)]
+mod common;
+
+use bindb::{Database, Table, ROps, RwOps};
+use cache::*;
+use common::*;
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+
+fn names<T>(rows: Vec<T>, name: impl Fn(T) -> String) -> Vec<String> {
+    let mut names: Vec<_> = rows.into_iter().map(name).collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_advisory_references() {
+    let db = cache::temporary();
+    let mut tx = db.wtx();
+    let first = advisory(&mut tx, "RHSA-2021:1024");
+    let second = advisory(&mut tx, "RHSA-2021:2048");
+    for cve in &["CVE-2021-3449", "CVE-2021-3450"] {
+        tx.put(&Cve { name: cve.to_string(), href: None });
+        tx.put(&AdvisoryCve(AdvisoryCveId { adv_id: first.id, cve: cve.to_string() }));
+    }
+    tx.put(&AdvisoryCve(AdvisoryCveId { adv_id: second.id, cve: "CVE-2021-3449".to_string() }));
+    tx.put(&Bugzilla { id: "1941554".to_string(), href: None, title: Some("openssl: NULL pointer dereference".to_string()) });
+    tx.put(&AdvisoryBugzilla(AdvisoryBugzillaId { adv_id: first.id, bug_id: "1941554".to_string() }));
+
+    assert_eq!(names(advisories_fixing(&tx, "CVE-2021-3449"), |a| a.name), vec!["RHSA-2021:1024", "RHSA-2021:2048"]);
+    assert_eq!(names(advisories_fixing(&tx, "CVE-2021-3450"), |a| a.name), vec!["RHSA-2021:1024"]);
+    assert!(advisories_fixing(&tx, "CVE-2000-0001").is_empty());
+    assert_eq!(names(cves_fixed_by(&tx, "RHSA-2021:1024"), |c| c.name), vec!["CVE-2021-3449", "CVE-2021-3450"]);
+    assert_eq!(names(cves_fixed_by(&tx, "RHSA-2021:2048"), |c| c.name), vec!["CVE-2021-3449"]);
+    assert!(cves_fixed_by(&tx, "RHSA-2000:0001").is_empty());
+    assert_eq!(names(bugs_fixed_by(&tx, "RHSA-2021:1024"), |b| b.id), vec!["1941554"]);
+    assert!(bugs_fixed_by(&tx, "RHSA-2021:2048").is_empty());
+
+    // CVEs and bugs are removed together with the last advisory fixing them
+    tx.delete::<Advisory>(&first.id);
+    assert_eq!(tx.get::<Cve>(&"CVE-2021-3450".to_string()), None);
+    assert_eq!(tx.get::<Bugzilla>(&"1941554".to_string()), None);
+    assert_eq!(names(cves_fixed_by(&tx, "RHSA-2021:2048"), |c| c.name), vec!["CVE-2021-3449"]);
+    tx.commit();
+}
+
+#[test]
+fn test_severity_date() {
+    assert_eq!(Severity::parse(Some("Important")), Severity::Important);
+    assert_eq!(Severity::parse(Some(" critical ")), Severity::Critical);
+    assert_eq!(Severity::parse(Some("Low")), Severity::Low);
+    assert_eq!(Severity::parse(Some("unknown")), Severity::None);
+    assert_eq!(Severity::parse(None), Severity::None);
+    assert!(Severity::Low < Severity::Moderate && Severity::Important < Severity::Critical);
+
+    let date = parse_date("2021-03-25 12:30:00").unwrap();
+    assert_eq!(date.to_rfc3339(), "2021-03-25T12:30:00+00:00");
+    assert_eq!(parse_date("2021-03-25").unwrap().to_rfc3339(), "2021-03-25T00:00:00+00:00");
+    assert_eq!(parse_date("1616675400"), Some(date));
+    assert_eq!(parse_date("25.03.2021"), None);
+    assert_eq!(parse_date(""), None);
+}
+
+#[test]
+fn test_advisory_migration() {
+    #[derive(Debug, Deserialize, Serialize, Table)]
+    #[bindb(name = "Advisory", codec = "bindb::Json")]
+    struct AdvisoryV0 {
+        #[bindb(key)]
+        id: Uuid,
+        r#type: String,
+        name: String,
+        summary: Option<String>,
+        desc: Option<String>,
+        issued: String,
+        updated: String,
+    }
+
+    let v0 = Database::temporary().register::<AdvisoryV0>();
+    let id = Uuid::new_v4();
+    v0.in_wtx(|tx| tx.put(&AdvisoryV0 {
+        id,
+        r#type: "bugfix".to_string(),
+        name: "RHBA-2021:1000".to_string(),
+        summary: Some("Bug fix update".to_string()),
+        desc: None,
+        issued: "2021-03-25".to_string(),
+        updated: "garbage".to_string(),
+    }));
+
+    let db = cache::open(v0.path());
+    let tx = db.tx();
+    let adv = tx.get_by::<AdvisoryNameIdx>(&"RHBA-2021:1000".to_string()).unwrap();
+    assert_eq!(adv.id, id);
+    // Fields missing in older versions are filled with placeholders
+    assert_eq!((adv.title.as_str(), adv.severity, adv.status.as_str()), ("RHBA-2021:1000", Severity::None, ""));
+    assert_eq!(adv.summary.as_deref(), Some("Bug fix update"));
+    assert_eq!(adv.issued, parse_date("2021-03-25"));
+    assert_eq!(adv.updated, None);
+    assert_eq!(tx.query::<Advisory>().by::<AdvisorySeverityIdx, _>(&Severity::None).fetch().len(), 1);
+    tx.commit();
+}
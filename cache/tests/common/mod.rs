@@ -0,0 +1,79 @@
+//! Rows shared by the cache tests
+#![allow(dead_code)]
+
+use bindb::{ROps, RwOps, Wtx};
+use cache::*;
+use uuid::Uuid;
+
+/// Repository of a fresh product and content set
+pub fn repo(tx: &mut Wtx, url: &str) -> Repo {
+    let product = Product { id: Uuid::new_v4(), name: format!("product {}", url) };
+    let cs = ContentSet {
+        id: Uuid::new_v4(),
+        label: format!("cs-{}", url),
+        name: format!("content set {}", url),
+        product_id: product.id,
+    };
+    let repo = Repo {
+        id: Uuid::new_v4(),
+        label: format!("repo-{}", url),
+        url: url.to_string(),
+        basearch: Some("x86_64".to_string()),
+        releasever: Some("8".to_string()),
+        revision: None,
+        content_set_id: cs.id,
+    };
+    tx.put(&product);
+    tx.put(&cs);
+    tx.put(&repo);
+    repo
+}
+
+pub fn pkg(tx: &mut Wtx, nevra: &str) -> Pkg {
+    let pkg = Pkg { id: Uuid::new_v4(), nevra: Nevra::parse(nevra).unwrap() };
+    tx.put(&pkg);
+    pkg
+}
+
+pub fn advisory(tx: &mut Wtx, name: &str) -> Advisory {
+    let adv = Advisory {
+        id: Uuid::new_v4(),
+        r#type: "security".to_string(),
+        name: name.to_string(),
+        title: format!("{}: security update", name),
+        severity: Severity::Important,
+        status: "final".to_string(),
+        from: "security@redhat.com".to_string(),
+        summary: None,
+        desc: None,
+        solution: None,
+        rights: None,
+        release: None,
+        issued: None,
+        updated: None,
+    };
+    tx.put(&adv);
+    adv
+}
+
+pub fn stream(tx: &mut Wtx, repo_id: Uuid, module: &str, name: &str) -> ModuleStream {
+    let module_id = match tx.get_by::<ModuleAttrsIdx>(&ModuleAttrs { repo_id, name: module.to_string(), arch: "x86_64".to_string() }) {
+        Some(module) => module.id,
+        None => {
+            let module = Module { id: Uuid::new_v4(), attrs: ModuleAttrs { repo_id, name: module.to_string(), arch: "x86_64".to_string() } };
+            tx.put(&module);
+            module.id
+        }
+    };
+    let stream = ModuleStream {
+        id: Uuid::new_v4(),
+        attrs: StreamAttrs { module_id, name: name.to_string(), version: 8040020210708, context: "522a0ee4".to_string() },
+        default: false,
+        summary: None,
+        description: None,
+        api: vec![],
+        components: vec![],
+    };
+    tx.put(&stream);
+    stream
+}
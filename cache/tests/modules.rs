@@ -0,0 +1,72 @@
+mod common;
+
+use bindb::RwOps;
+use cache::*;
+use common::*;
+
+#[test]
+fn test_stream_packages() {
+    let db = cache::temporary();
+    let mut tx = db.wtx();
+    let repo = repo(&mut tx, "https://cdn/appstream");
+    let node14 = stream(&mut tx, repo.id, "nodejs", "14");
+    let node16 = stream(&mut tx, repo.id, "nodejs", "16");
+    let shared = Nevra::parse("nodejs-packaging-23-3.module+el8.3.0.noarch").unwrap();
+    let node = Nevra::parse("nodejs-1:14.17.0-1.module+el8.4.0.x86_64").unwrap();
+    let docs = Nevra::parse("nodejs-docs-1:14.17.0-1.module+el8.4.0.noarch").unwrap();
+    for nevra in &[&shared, &node, &docs] {
+        tx.put(&StreamPkg(StreamPkgId { nevra: (*nevra).clone(), stream_id: node14.id }));
+    }
+    tx.put(&StreamPkg(StreamPkgId { nevra: shared.clone(), stream_id: node16.id }));
+
+    let mut streams: Vec<_> = streams_of(&tx, &shared).into_iter().map(|s| s.attrs.name).collect();
+    streams.sort();
+    assert_eq!(streams, vec!["14", "16"]);
+    // Prefix of the key is the whole nevra, packages sharing a name prefix don't match
+    assert_eq!(streams_of(&tx, &node), vec![node14.clone()]);
+    assert!(streams_of(&tx, &Nevra::parse("bash-5.1.8-6.el9.x86_64").unwrap()).is_empty());
+
+    let mut packages = packages_of(&tx, &node14.id);
+    packages.sort_by(|a, b| a.name.cmp(&b.name));
+    assert_eq!(packages, vec![node, docs, shared.clone()]);
+    assert_eq!(packages_of(&tx, &node16.id), vec![shared.clone()]);
+
+    // Links are removed with the stream
+    tx.delete::<ModuleStream>(&node14.id);
+    assert!(packages_of(&tx, &node14.id).is_empty());
+    assert_eq!(streams_of(&tx, &shared), vec![node16]);
+    tx.commit();
+}
+
+#[test]
+fn test_module_defaults() {
+    let db = cache::temporary();
+    let mut tx = db.wtx();
+    let other = repo(&mut tx, "https://cdn/other");
+    let repo = repo(&mut tx, "https://cdn/appstream");
+    let mut profiles = std::collections::BTreeMap::new();
+    profiles.insert("14".to_string(), vec!["common".to_string()]);
+    let default = ModuleDefault {
+        id: ModuleDefaultId { repo_id: repo.id, module: "nodejs".to_string() },
+        stream: Some("14".to_string()),
+        profiles,
+        intents: Default::default(),
+    };
+    tx.put(&default);
+
+    assert_eq!(default_of(&tx, repo.id, "nodejs"), Some(default.clone()));
+    assert_eq!(default_of(&tx, repo.id, "python38"), None);
+    // Defaults belong to a single repository
+    assert_eq!(default_of(&tx, other.id, "nodejs"), None);
+
+    assert!(default.is_default("14"));
+    assert!(!default.is_default("16"));
+    assert_eq!(default.profiles_of("14"), &["common".to_string()]);
+    assert!(default.profiles_of("16").is_empty());
+    let none = ModuleDefault { stream: None, ..default };
+    assert!(!none.is_default("14"));
+
+    tx.delete::<Repo>(&repo.id);
+    assert_eq!(default_of(&tx, repo.id, "nodejs"), None);
+    tx.commit();
+}
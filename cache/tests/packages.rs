@@ -0,0 +1,110 @@
+mod common;
+
+use bindb::{ROps, RwOps};
+use cache::*;
+use common::*;
+
+#[test]
+fn test_nevra_parse() {
+    let nevra = Nevra::parse("nodejs-1:14.17.0-1.module+el8.4.0.x86_64").unwrap();
+    assert_eq!(nevra, Nevra {
+        name: "nodejs".to_string(),
+        epoch: 1,
+        ver: "14.17.0".to_string(),
+        rel: "1.module+el8.4.0".to_string(),
+        arch: "x86_64".to_string(),
+    });
+
+    // Dashes in the name are kept, missing epoch is 0
+    let nevra = Nevra::parse("python3-libs-3.6.8-37.el8.i686").unwrap();
+    assert_eq!((nevra.name.as_str(), nevra.epoch, nevra.ver.as_str()), ("python3-libs", 0, "3.6.8"));
+    assert_eq!((nevra.rel.as_str(), nevra.arch.as_str()), ("37.el8", "i686"));
+
+    assert_eq!(Nevra::parse("noarch"), None);
+    assert_eq!(Nevra::parse("bash-5.1.x86_64"), None);
+    assert_eq!(Nevra::parse("bash-x:5.1-2.x86_64"), None);
+}
+
+#[test]
+fn test_src_pkg_filename() {
+    let src = SrcPkg::new(uuid::Uuid::new_v4(), "openssl-3.0.7-1.el9.src.rpm".to_string());
+    let nevra = src.nevra.unwrap();
+    assert_eq!((nevra.name.as_str(), nevra.epoch), ("openssl", 0));
+    assert_eq!((nevra.ver.as_str(), nevra.rel.as_str(), nevra.arch.as_str()), ("3.0.7", "1.el9", "src"));
+
+    // Unparseable file names are still stored
+    assert_eq!(SrcPkg::new(uuid::Uuid::new_v4(), "openssl-3.0.7-1.el9.src".to_string()).nevra, None);
+    assert_eq!(SrcPkg::new(uuid::Uuid::new_v4(), "garbage.rpm".to_string()).nevra, None);
+}
+
+#[test]
+fn test_pkg_detail_location() {
+    let db = cache::temporary();
+    let mut tx = db.wtx();
+    let repo = repo(&mut tx, "https://cdn/repo");
+    let pkg = pkg(&mut tx, "bash-5.1.8-6.el9.x86_64");
+
+    let detail = PkgDetail {
+        pkg_id: pkg.id,
+        summary: "The GNU Bourne Again shell".to_string(),
+        description: "The GNU Bourne Again shell (Bash) is a shell".repeat(20),
+        checksum: Some(Checksum { r#type: "sha256".to_string(), value: "ab12".to_string() }),
+        packager: None,
+        url: Some("https://www.gnu.org/software/bash".to_string()),
+        build_time: 1_630_000_000,
+        file_time: 1_630_000_100,
+        package_size: 1_800_000,
+        installed_size: 7_700_000,
+        archive_size: 7_800_000,
+        source_rpm: Some("bash-5.1.8-6.el9.src.rpm".to_string()),
+    };
+    let location = PkgLocation {
+        id: PkgRepoId { pkg_id: pkg.id, repo_id: repo.id },
+        href: "Packages/b/bash-5.1.8-6.el9.x86_64.rpm".to_string(),
+    };
+    tx.put(&detail);
+    tx.put(&location);
+    tx.commit();
+
+    let tx = db.tx();
+    assert_eq!(tx.get_by::<PkgNevraIdx>(&pkg.nevra).map(|p| p.id), Some(pkg.id));
+    assert_eq!(tx.get::<PkgDetail>(&pkg.id), Some(detail));
+    assert_eq!(tx.get::<PkgLocation>(&location.id), Some(location.clone()));
+    tx.commit();
+
+    // Location goes away with the repository, details stay with the package
+    let mut tx = db.wtx();
+    tx.delete::<Repo>(&repo.id);
+    assert_eq!(tx.get::<PkgLocation>(&location.id), None);
+    assert!(tx.get::<PkgDetail>(&pkg.id).is_some());
+    tx.delete::<Pkg>(&pkg.id);
+    assert_eq!(tx.get::<PkgDetail>(&pkg.id), None);
+    tx.commit();
+}
+
+#[test]
+fn test_source_binaries() {
+    let db = cache::temporary();
+    let mut tx = db.wtx();
+    let src = SrcPkg::new(uuid::Uuid::new_v4(), "openssl-3.0.7-1.el9.src.rpm".to_string());
+    let libs = pkg(&mut tx, "openssl-libs-1:3.0.7-1.el9.x86_64");
+    let bin = pkg(&mut tx, "openssl-1:3.0.7-1.el9.x86_64");
+    let other = pkg(&mut tx, "bash-5.1.8-6.el9.x86_64");
+    tx.put(&src);
+    tx.put(&PkgSrc { pkg_id: libs.id, src_id: src.id });
+    tx.put(&PkgSrc { pkg_id: bin.id, src_id: src.id });
+
+    assert_eq!(source_of(&tx, &libs.id), Some(src.clone()));
+    assert_eq!(source_of(&tx, &other.id), None);
+    let mut binaries: Vec<_> = binaries_of(&tx, &src.filename).into_iter().map(|p| p.nevra.name).collect();
+    binaries.sort();
+    assert_eq!(binaries, vec!["openssl", "openssl-libs"]);
+    assert!(binaries_of(&tx, "bash-5.1.8-6.el9.src.rpm").is_empty());
+
+    // Source package is removed together with its last binary
+    tx.delete::<Pkg>(&libs.id);
+    assert_eq!(binaries_of(&tx, &src.filename).len(), 1);
+    tx.delete::<Pkg>(&bin.id);
+    assert_eq!(tx.get::<SrcPkg>(&src.id), None);
+    tx.commit();
+}
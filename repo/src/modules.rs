@@ -75,9 +75,18 @@ pub struct ModuleMDData {
     pub artifacts: Option<Rpms<Vec<String>>>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Intent {
+    pub stream: Option<String>,
+    #[serde(default)]
+    pub profiles: HashMap<String, Vec<String>>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DefaultsData {
     pub module: String,
     pub stream: Option<String>,
     pub profiles: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    pub intents: HashMap<String, Intent>,
 }
\ No newline at end of file
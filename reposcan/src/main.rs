@@ -2,44 +2,144 @@
 #![feature(generic_associated_types)]
 
 mod repolist;
+#[cfg(test)]
+mod tests;
 
 use rpmsync::Syncer;
-use rpmrepo::repomd::RepoMD;
+use rpmrepo::repomd::{RepoMD, Type};
 use rpmrepo::primary::Package;
-use rpmrepo::modules::Chunk;
+use rpmrepo::modules::{Chunk, DefaultsData, Depencency, Profiles};
 use rpmrepo::updateinfo::{Update};
 use anyhow::*;
 use itertools::Itertools;
 use rayon::prelude::{ParallelBridge, ParallelIterator};
 use uuid::Uuid;
-use std::collections::HashMap;
+use chrono::Utc;
+use std::collections::{HashMap, HashSet, BTreeMap};
 use cache::*;
-use bindb::{Database, RwOps, ROps};
+use bindb::{Database, RwOps, ROps, Wtx, Id};
 
 
 pub struct RepoScanner {
     repo: Repo,
     db: Database,
+    /// Record of the current sync attempt, stored once it finishes
+    sync: RepoSync,
+}
+
+/// Packages, advisories and module streams linked to a repo before the sync, and those found in the new revision
+#[derive(Default)]
+pub struct RepoDiff {
+    old_pkgs: HashSet<Uuid>,
+    old_advs: HashSet<Uuid>,
+    old_streams: HashSet<Uuid>,
+    new_pkgs: HashSet<Uuid>,
+    new_advs: HashSet<Uuid>,
+    new_streams: HashSet<Uuid>,
+}
+
+impl RepoDiff {
+    fn load(tx: &Wtx, repo_id: Uuid) -> Self {
+        RepoDiff {
+            old_pkgs: tx.referencing::<PkgRepoRepoRef>(&repo_id).into_iter().map(|id| id.pkg_id).collect(),
+            old_advs: tx.referencing::<AdvisoryRepoRepoRef>(&repo_id).into_iter().map(|id| id.adv_id).collect(),
+            old_streams: tx.referencing::<ModuleRepoRef>(&repo_id)
+                .into_iter()
+                .flat_map(|id| tx.referencing::<ModuleStreamModuleRef>(&id))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Unlink packages and advisories, which are missing from the new revision. Packages and advisories
+    /// are owned by their repo links, so rows no longer linked to any repo are deleted along with them.
+    /// Module streams belong to a single repo, missing ones are deleted with their packages, profiles
+    /// and dependencies, and so are modules left without streams. Advisory links naming a missing stream
+    /// are deleted too, they only hold its id.
+    fn remove_stale(&self, tx: &mut Wtx, repo_id: Uuid, sync: &mut RepoSync) {
+        let pkgs = self.old_pkgs.difference(&self.new_pkgs).collect::<Vec<_>>();
+        let advs = self.old_advs.difference(&self.new_advs).collect::<Vec<_>>();
+        let streams = self.old_streams.difference(&self.new_streams).collect::<HashSet<_>>();
+
+        // Streams of this repo are only linked to its packages
+        if !streams.is_empty() {
+            for pkg_id in &self.old_pkgs {
+                for id in tx.referencing::<PkgAdvisoryPkgRef>(pkg_id) {
+                    if matches!(&id.stream_id, Some(s) if streams.contains(s)) {
+                        tx.delete::<PkgAdvisory>(&id);
+                    }
+                }
+            }
+        }
+        for stream_id in streams {
+            tx.delete::<ModuleStream>(stream_id);
+        }
+        for module_id in tx.referencing::<ModuleRepoRef>(&repo_id) {
+            if tx.referencing::<ModuleStreamModuleRef>(&module_id).is_empty() {
+                tx.delete::<Module>(&module_id);
+            }
+        }
+
+        for pkg_id in &pkgs {
+            let id = PkgRepoId { pkg_id: **pkg_id, repo_id };
+            tx.delete::<PkgLocation>(&id);
+            tx.delete::<PkgRepo>(&id);
+        }
+        for adv_id in &advs {
+            tx.delete::<AdvisoryRepo>(&AdvisoryRepoId { adv_id: **adv_id, repo_id });
+        }
+
+        sync.pkgs_removed += pkgs.len() as u64;
+        sync.advs_removed += advs.len() as u64;
+    }
 }
 
 impl rpmsync::MetadataTarget for RepoScanner {
     fn on_metadata(&mut self, syncer: &Syncer, md: RepoMD) {
         let old = self.db.in_tx(|tx| tx.get_by::<RepoUrl>(&self.repo.url));
+        self.sync.new_revision = Some(md.revision.clone());
+        self.sync.primary_checksum = md.find_item(Type::Primary).map(|i| format!("{}:{}", i.checksum.typ, i.checksum.value));
 
         if old.as_ref().and_then(|r| r.revision.as_ref())  != Some(&md.revision) {
             println!("{:?} is outdated, syncing", self.repo.url);
+            self.repo.id = old.map(|v| v.id).unwrap_or_else(|| self.db.generate_id());
+            let repo_id = self.repo.id;
 
-            syncer.sync_packages_streaming(&mut PackageScanner { base: self, packages: vec![] }, &md);
-            syncer.sync_updates_streaming(&mut UpdateScanner { base: self, advs: vec![] }, &md);
-            syncer.sync_modules(&mut ModuleScanner { base: self, defaults: HashMap::new(), module_ids: HashMap::new() }, &md);
+            // All metadata of the revision is staged in scanners first, nothing is written until it is complete
+            let mut packages = PackageScanner { repo_id, packages: vec![] };
+            let mut modules = ModuleScanner { repo_id, modules: vec![], defaults: vec![] };
+            let mut updates = UpdateScanner { repo_id, advs: vec![] };
+
+            let mut errors = vec![];
+            if let Err(e) = syncer.sync_packages_streaming(&mut packages, &md) {
+                errors.push(format!("packages: {:?}", e));
+            }
+            if let Err(e) = syncer.sync_modules(&mut modules, &md) {
+                errors.push(format!("modules: {:?}", e));
+            }
+            if let Err(e) = syncer.sync_updates_streaming(&mut updates, &md) {
+                errors.push(format!("updates: {:?}", e));
+            }
+            if !errors.is_empty() {
+                // Staged data is dropped, the previous revision stays intact and the next sync retries
+                println!("{:?} failed to sync: {}", self.repo.url, errors.join(", "));
+                self.sync.error = Some(errors.join("\n"));
+                return;
+            }
 
-            self.repo.id = old.map(|v| v.id).unwrap_or_else(|| self.db.generate_id());
             self.repo.revision = Some(md.revision as _);
 
-            let repo = self.repo.clone();
-            self.db.in_wtx(|tx| {
-                tx.put(&repo);
-            });
+            // The new revision replaces the previous one in a single transaction. Readers see either of them
+            // as a whole, and a failure before the commit leaves the previous one untouched.
+            let mut tx = self.db.wtx();
+            let mut diff = RepoDiff::load(&tx, repo_id);
+            packages.write(&mut tx, &mut diff, &mut self.sync);
+            // Modules go before updates, which resolve default streams from stored module defaults
+            modules.write(&mut tx, &mut diff);
+            updates.write(&mut tx, &mut diff, &mut self.sync);
+            diff.remove_stale(&mut tx, repo_id, &mut self.sync);
+            tx.put(&self.repo);
+            tx.commit();
         } else {
             println!("{:?} is up to date", self.repo.url);
         }
@@ -47,12 +147,12 @@ impl rpmsync::MetadataTarget for RepoScanner {
 }
 
 
-pub struct PackageScanner<'a> {
-    base: &'a mut RepoScanner,
-    packages: Vec<Pkg>,
+pub struct PackageScanner {
+    repo_id: Uuid,
+    packages: Vec<(Pkg, PkgDetail, String)>,
 }
 
-impl<'a> rpmsync::PackageTarget for PackageScanner<'a> {
+impl rpmsync::PackageTarget for PackageScanner {
     fn on_package(&mut self, p: Package) {
         let nevra = Nevra {
             name: p.name,
@@ -61,39 +161,102 @@ impl<'a> rpmsync::PackageTarget for PackageScanner<'a> {
             rel: p.version.rel,
             arch: p.arch,
         };
-        self.packages.push(Pkg {
+        let pkg = Pkg {
             id: Uuid::new_v4(),
             nevra,
-        });
+        };
+        let detail = PkgDetail {
+            pkg_id: pkg.id,
+            summary: p.summary,
+            description: p.description,
+            checksum: p.checksum.map(|c| Checksum { r#type: c.typ, value: c.value }),
+            packager: p.packager,
+            url: p.url,
+            build_time: p.time.build as u64,
+            file_time: p.time.file as u64,
+            package_size: p.size.package as u64,
+            installed_size: p.size.installed as u64,
+            archive_size: p.size.archive as u64,
+            source_rpm: p.format.map(|f| f.source),
+        };
+        self.packages.push((pkg, detail, p.location.href));
     }
 
-    fn done(&mut self) {
-        let repo_id = self.base.repo.id;
+    fn done(&mut self) {}
+}
+
+impl PackageScanner {
+    fn write(&mut self, tx: &mut Wtx, diff: &mut RepoDiff, sync: &mut RepoSync) {
+        let repo_id = self.repo_id;
         let pkgs = std::mem::replace(&mut self.packages, vec![]);
-        self.base.db.in_wtx(|tx| {
-            for mut pkg in pkgs {
-                tx.put_by::<PkgNevraIdx>(&mut pkg);
-                tx.put(&PkgRepo(PkgRepoId { pkg_id: pkg.id, repo_id }));
+        let mut sources = HashMap::new();
+        let mut added = 0;
+        for (mut pkg, mut detail, href) in pkgs {
+            if let Some(old) = tx.get_by::<PkgNevraIdx>(&pkg.nevra) {
+                // Packages linked only through updateinfo have no details yet
+                if diff.old_pkgs.contains(&old.id) && tx.get::<PkgDetail>(&old.id).is_some() {
+                    // Package is already in the repo, its details do not change, only its location might
+                    let id = PkgRepoId { pkg_id: old.id, repo_id };
+                    if tx.get::<PkgLocation>(&id).map_or(true, |l| l.href != href) {
+                        tx.put(&PkgLocation { id, href });
+                    }
+                    diff.new_pkgs.insert(old.id);
+                    continue;
+                }
             }
-        });
+
+            tx.put_by::<PkgNevraIdx>(&mut pkg);
+            detail.pkg_id = pkg.id;
+            if let Some(filename) = &detail.source_rpm {
+                let src_id = *sources.entry(filename.clone()).or_insert_with(|| {
+                    let mut src = SrcPkg::new(Uuid::new_v4(), filename.clone());
+                    tx.put_by::<SrcPkgFilenameIdx>(&mut src);
+                    src.id
+                });
+                tx.put(&PkgSrc { pkg_id: pkg.id, src_id });
+            }
+            tx.put(&detail);
+            let id = PkgRepoId { pkg_id: pkg.id, repo_id };
+            tx.put(&PkgLocation { id, href });
+            tx.put(&PkgRepo(id));
+            if diff.new_pkgs.insert(pkg.id) && !diff.old_pkgs.contains(&pkg.id) {
+                added += 1;
+            }
+        }
+        sync.pkgs_added += added;
     }
 }
 
-pub struct UpdateScanner<'a> {
-    base: &'a mut RepoScanner,
-    advs: Vec<(Advisory, Vec<(Pkg, Option<rpmrepo::updateinfo::Module>)>)>,
+/// Advisory parsed from updateinfo, along with rows linked to it
+pub struct ParsedUpdate {
+    adv: Advisory,
+    pkgs: Vec<(Pkg, Option<rpmrepo::updateinfo::Module>)>,
+    cves: Vec<Cve>,
+    bugs: Vec<Bugzilla>,
 }
 
-impl<'a> rpmsync::UpdateTarget for UpdateScanner<'a> {
+pub struct UpdateScanner {
+    repo_id: Uuid,
+    advs: Vec<ParsedUpdate>,
+}
+
+impl rpmsync::UpdateTarget for UpdateScanner {
     fn on_update(&mut self, up: Update) {
         let adv = Advisory {
             id: Uuid::new_v4(),
             name: up.id.clone(),
+            title: up.title,
+            severity: Severity::parse(up.severity.as_deref()),
+            status: up.status,
+            from: up.from,
             desc: up.description,
             summary: up.summary,
+            solution: up.solution,
+            rights: up.rights,
+            release: up.release,
             r#type: up.typ,
-            issued: up.issued.date,
-            updated: up.updated.date,
+            issued: parse_date(&up.issued.date),
+            updated: parse_date(&up.updated.date),
         };
 
         let mut pkgs = vec![];
@@ -119,75 +282,159 @@ impl<'a> rpmsync::UpdateTarget for UpdateScanner<'a> {
                 pkgs.push((p, m.clone()));
             }
         }
-        self.advs.push((adv, pkgs));
+
+        let mut cves = vec![];
+        let mut bugs = vec![];
+        for r in up.references {
+            match (r.typ.as_deref(), r.id) {
+                (Some("cve"), Some(name)) => cves.push(Cve { name, href: r.href }),
+                (Some("bugzilla"), Some(id)) => bugs.push(Bugzilla { id, href: r.href, title: r.title }),
+                _ => {}
+            }
+        }
+        self.advs.push(ParsedUpdate { adv, pkgs, cves, bugs });
     }
 
-    fn done(&mut self) {
-        let repo_id = self.base.repo.id;
+    fn done(&mut self) {}
+}
+
+impl UpdateScanner {
+    fn write(&mut self, tx: &mut Wtx, diff: &mut RepoDiff, sync: &mut RepoSync) {
+        let repo_id = self.repo_id;
         let advisories = std::mem::replace(&mut self.advs, vec![]);
-        self.base.db.in_wtx(|tx| {
-            for (mut adv, pkgs) in advisories {
-                tx.put_by::<AdvisoryNameIdx>(&mut adv);
-                tx.put(&AdvisoryRepo(AdvisoryRepoId { adv_id: adv.id, repo_id }));
-
-                for (mut pkg, module) in pkgs {
-                    let stream_id = if let Some(mod_data) = module {
-                        let mut newmod = Module {
-                            id: Uuid::new_v4(),
-                            attrs: ModuleAttrs {
-                                name: mod_data.name,
-                                arch: mod_data.arch,
-                                repo_id,
-                            },
-                        };
-
-                        tx.put_by::<ModuleAttrsIdx>(&mut newmod);
-
-                        let mut stream = ModuleStream {
-                            id: Uuid::new_v4(),
-                            attrs: StreamAttrs {
-                                module_id: newmod.id,
-                                name: mod_data.stream,
-                                version: mod_data.version,
-                                context: mod_data.context,
-                            },
-                            // TODO: Update default from proper location
-                            default: false,
-                        };
-                        tx.put_by::<StreamAttrsIdx>(&mut stream);
-                        Some(stream.id)
-                    } else { None };
-
-                    tx.put_by::<PkgNevraIdx>(&mut pkg);
-                    tx.put(&PkgRepo(PkgRepoId { pkg_id: pkg.id, repo_id }));
-                    tx.put(&PkgAdvisory(PkgAdvisoryId { pkg_id: pkg.id, adv_id: adv.id, stream_id }));
+        let mut added = 0;
+        let mut pkgs_added = 0;
+        for ParsedUpdate { mut adv, pkgs, cves, bugs } in advisories {
+            tx.put_by::<AdvisoryNameIdx>(&mut adv);
+            tx.put(&AdvisoryRepo(AdvisoryRepoId { adv_id: adv.id, repo_id }));
+            if diff.new_advs.insert(adv.id) && !diff.old_advs.contains(&adv.id) {
+                added += 1;
+            }
+
+            // References dropped from the advisory are unlinked, CVEs and bugs are removed with their last link
+            let new_cves = cves.iter().map(|c| c.name.clone()).collect::<HashSet<_>>();
+            for id in tx.referencing::<AdvisoryCveAdvisoryRef>(&adv.id) {
+                if !new_cves.contains(&id.cve) {
+                    tx.delete::<AdvisoryCve>(&id);
                 }
             }
-        })
+            let new_bugs = bugs.iter().map(|b| b.id.clone()).collect::<HashSet<_>>();
+            for id in tx.referencing::<AdvisoryBugzillaAdvisoryRef>(&adv.id) {
+                if !new_bugs.contains(&id.bug_id) {
+                    tx.delete::<AdvisoryBugzilla>(&id);
+                }
+            }
+            for cve in cves {
+                tx.put(&cve);
+                tx.put(&AdvisoryCve(AdvisoryCveId { adv_id: adv.id, cve: cve.name }));
+            }
+            for bug in bugs {
+                tx.put(&bug);
+                tx.put(&AdvisoryBugzilla(AdvisoryBugzillaId { adv_id: adv.id, bug_id: bug.id }));
+            }
+
+            let mut links = HashSet::new();
+            for (mut pkg, module) in pkgs {
+                let stream_id = if let Some(mod_data) = module {
+                    let mut newmod = Module {
+                        id: Uuid::new_v4(),
+                        attrs: ModuleAttrs {
+                            name: mod_data.name,
+                            arch: mod_data.arch,
+                            repo_id,
+                        },
+                    };
+
+                    tx.put_by::<ModuleAttrsIdx>(&mut newmod);
+
+                    let stream_name = &mod_data.stream;
+                    let default = default_of(&*tx, repo_id, &newmod.attrs.name)
+                        .map_or(false, |d| d.is_default(stream_name));
+                    let mut stream = ModuleStream {
+                        id: Uuid::new_v4(),
+                        attrs: StreamAttrs {
+                            module_id: newmod.id,
+                            name: mod_data.stream,
+                            version: mod_data.version,
+                            context: mod_data.context,
+                        },
+                        default,
+                        summary: None,
+                        description: None,
+                        api: vec![],
+                        components: vec![],
+                    };
+                    // Streams known from modulemd carry more data, keep them as they are
+                    tx.put_by_with::<StreamAttrsIdx, _>(&mut stream, |old, new| *new = old.clone());
+                    diff.new_streams.insert(stream.id);
+                    Some(stream.id)
+                } else { None };
+
+                tx.put_by::<PkgNevraIdx>(&mut pkg);
+                tx.put(&PkgRepo(PkgRepoId { pkg_id: pkg.id, repo_id }));
+                if diff.new_pkgs.insert(pkg.id) && !diff.old_pkgs.contains(&pkg.id) {
+                    pkgs_added += 1;
+                }
+                let link = PkgAdvisoryId { pkg_id: pkg.id, adv_id: adv.id, stream_id };
+                tx.put(&PkgAdvisory(link.clone()));
+                links.insert(link);
+            }
+            // Advisories are shared by repos, links of packages from other repos are left to their own syncs
+            for id in tx.referencing::<PkgAdvisoryAdvisoryRef>(&adv.id) {
+                if diff.old_pkgs.contains(&id.pkg_id) && !links.contains(&id) {
+                    tx.delete::<PkgAdvisory>(&id);
+                }
+            }
+        }
+        sync.advs_added += added;
+        sync.pkgs_added += pkgs_added;
     }
 }
 
-#[allow(dead_code)]
-pub struct ModuleScanner<'a> {
-    base: &'a mut RepoScanner,
-    module_ids: HashMap<String, Uuid>,
-    defaults: HashMap<String, String>,
+/// Module stream parsed from modulemd, along with data of rows linked to it
+pub struct ParsedStream {
+    module: Module,
+    stream: ModuleStream,
+    artifacts: Vec<String>,
+    profiles: Profiles,
+    dependencies: Vec<Depencency>,
 }
 
-impl rpmsync::ModuleTarget for ModuleScanner<'_> {
+pub struct ModuleScanner {
+    repo_id: Uuid,
+    modules: Vec<ParsedStream>,
+    defaults: Vec<DefaultsData>,
+}
+
+fn module_default(repo_id: Uuid, def: DefaultsData) -> ModuleDefault {
+    ModuleDefault {
+        id: ModuleDefaultId { repo_id, module: def.module },
+        stream: def.stream,
+        profiles: def.profiles.into_iter().collect(),
+        intents: def.intents
+            .into_iter()
+            .map(|(name, intent)| (name, ModuleIntent {
+                stream: intent.stream,
+                profiles: intent.profiles.into_iter().collect(),
+            }))
+            .collect(),
+    }
+}
+
+impl rpmsync::ModuleTarget for ModuleScanner {
     fn on_module_chunk(&mut self, _chunk: Chunk) {
         match _chunk {
             Chunk::ModuleMd(_md) => {
-                let mut module = Module {
+                let module = Module {
                     id: Uuid::new_v4(),
                     attrs: ModuleAttrs {
                         name: _md.name,
-                        repo_id: self.base.repo.id,
+                        repo_id: self.repo_id,
                         arch: _md.arch,
                     },
                 };
 
-                let mut stream = ModuleStream {
+                let stream = ModuleStream {
                     id: Uuid::new_v4(),
                     attrs: StreamAttrs {
                         name: _md.stream,
@@ -196,39 +443,117 @@ impl rpmsync::ModuleTarget for ModuleScanner<'_> {
                         module_id: Uuid::new_v4(),
                     },
                     default: false,
+                    summary: _md.summary,
+                    description: _md.description,
+                    api: _md.api.map(|api| api.rpms).unwrap_or_default(),
+                    components: _md.components.map(|c| c.rpms.into_keys().collect()).unwrap_or_default(),
                 };
 
-                self.base.db.in_wtx(|tx| {
-                    tx.put_by::<ModuleAttrsIdx>(&mut module);
-                    stream.attrs.module_id = module.id;
-                    tx.put_by_with::<StreamAttrsIdx, _>(&mut stream, |old, new| {
-                        new.default = old.default
-                    });
+                self.modules.push(ParsedStream {
+                    module,
+                    stream,
+                    artifacts: _md.artifacts.map(|a| a.rpms).unwrap_or_default(),
+                    profiles: _md.profiles.unwrap_or_default(),
+                    dependencies: _md.dependencies,
                 });
-
-                self.module_ids.insert(module.attrs.name.clone(), module.id);
             }
             Chunk::Defaults(_def) => {
-                if let Some(default) = _def.stream {
-                    self.defaults.insert(_def.module, default);
-                }
+                self.defaults.push(_def);
             }
         }
     }
 
-    fn done(&mut self) {
-        let module_ids = std::mem::replace(&mut self.module_ids, HashMap::new());
-        for (module, s) in std::mem::replace(&mut self.defaults, HashMap::new()) {
-            self.base.db.in_wtx(|tx| {
-                let streams = tx.scan().filter(|stream: &ModuleStream| {
-                    stream.attrs.module_id == *module_ids.get(&module).unwrap()
-                }).map(|stream| stream.clone()).collect::<Vec<_>>();
-
-                for mut stream in streams {
-                    stream.default = stream.attrs.name == *s;
-                    tx.put(&stream);
+    fn done(&mut self) {}
+}
+
+impl ModuleScanner {
+    fn write(&mut self, tx: &mut Wtx, diff: &mut RepoDiff) {
+        let repo_id = self.repo_id;
+        let defaults = std::mem::replace(&mut self.defaults, vec![])
+            .into_iter()
+            .map(|def| (def.module.clone(), module_default(repo_id, def)))
+            .collect::<HashMap<_, _>>();
+
+        // Modules, which lost their defaults, need their streams reset as well
+        let mut names = defaults.keys().cloned().collect::<HashSet<_>>();
+        for id in tx.referencing::<ModuleDefaultRepoRef>(&repo_id) {
+            if !defaults.contains_key(&id.module) {
+                tx.delete::<ModuleDefault>(&id);
+                names.insert(id.module);
+            }
+        }
+        for default in defaults.values() {
+            tx.put(default);
+        }
+
+        for parsed in std::mem::replace(&mut self.modules, vec![]) {
+            let ParsedStream { mut module, mut stream, artifacts, profiles, dependencies } = parsed;
+            tx.put_by::<ModuleAttrsIdx>(&mut module);
+            stream.attrs.module_id = module.id;
+            stream.default = defaults.get(&module.attrs.name).map_or(false, |d| d.is_default(&stream.attrs.name));
+            // Keep the id, rows linked to the stream refer to it
+            tx.put_by_with::<StreamAttrsIdx, _>(&mut stream, |old, new| new.id = old.id);
+            diff.new_streams.insert(stream.id);
+
+            // Rows linked to the stream are replaced by those of the new revision
+            for id in tx.referencing::<StreamPkgModuleStreamRef>(&stream.id) {
+                tx.delete::<StreamPkg>(&id);
+            }
+            for id in tx.referencing::<StreamProfileModuleStreamRef>(&stream.id) {
+                tx.delete::<StreamProfile>(&id);
+            }
+            for id in tx.referencing::<StreamDepModuleStreamRef>(&stream.id) {
+                tx.delete::<StreamDep>(&id);
+            }
+            for nevra in artifacts.iter().filter_map(|a| Nevra::parse(a)) {
+                tx.put(&StreamPkg(StreamPkgId { nevra, stream_id: stream.id }));
+            }
+            for (name, profile) in profiles {
+                tx.put(&StreamProfile {
+                    id: StreamProfileId { stream_id: stream.id, name },
+                    description: profile.description,
+                    rpms: profile.rpms,
+                });
+            }
+
+            // Alternative dependency sets are merged, streams required by any of them are accepted
+            let mut deps = BTreeMap::<(String, bool), Vec<Vec<String>>>::new();
+            for dep in dependencies {
+                let requires = dep.requires.into_iter().map(|r| (r, false));
+                let buildrequires = dep.buildrequires.into_iter().map(|r| (r, true));
+                for ((required, streams), build) in requires.chain(buildrequires) {
+                    deps.entry((required, build)).or_default().push(streams);
                 }
-            });
+            }
+            for ((module, build), alternatives) in deps {
+                // Empty list accepts any stream, so it also does after merging
+                let mut streams = if alternatives.iter().any(Vec::is_empty) {
+                    vec![]
+                } else {
+                    alternatives.concat()
+                };
+                streams.sort();
+                streams.dedup();
+                tx.put(&StreamDep { id: StreamDepId { stream_id: stream.id, module, build }, streams });
+            }
+        }
+
+        // Streams only referenced by updateinfo are missing from modulemd, update them through module lookups.
+        // Defaults may name modules absent from the repo, those have nothing to update.
+        for name in names {
+            let default = defaults.get(&name);
+            let modules = tx.query::<Module>().by::<ModuleAttrsIdx, _>(&(repo_id, &name)).fetch();
+            for module in modules {
+                for id in tx.referencing::<ModuleStreamModuleRef>(&module.id) {
+                    if let Some(mut stream) = tx.get::<ModuleStream>(&id) {
+                        let is_default = default.map_or(false, |d| d.is_default(&stream.attrs.name));
+                        if stream.default != is_default {
+                            stream.default = is_default;
+                            tx.put(&stream);
+                        }
+                    }
+                }
+            }
         }
     }
 }
@@ -241,22 +566,38 @@ pub struct Reposcan {
 impl Reposcan {
     pub fn new() -> Result<Self> {
         Ok(Reposcan {
-            db: Database::open("data.mdbx")
-                .register::<Repo>()
-                .register::<Pkg>()
-                .register::<Advisory>()
-                .register::<Module>()
-                .register::<ModuleStream>()
-                .register::<PkgAdvisory>()
-                .register::<PkgRepo>()
-                .register::<AdvisoryRepo>()
-            ,
+            db: cache::open("data.mdbx"),
         })
     }
 
+    /// Store products, content sets and repos of the repolist. Entities, which are missing from the repolist,
+    /// are removed along with all data of their repos. Readers see either the previous repolist or the new one.
     pub fn load_repolist(&mut self, rl: repolist::Repolist) -> Result<()> {
-        for (_p, prod) in rl.iter().flat_map(|p| &p.products) {
+        let mut tx = self.db.wtx();
+        let mut products = HashSet::new();
+        // Content sets are looked up by label alone, the same label in two products would merge them
+        let mut labels = HashMap::new();
+        let mut content_sets = HashSet::new();
+        let mut repo_ids = HashSet::new();
+
+        for (name, prod) in rl.iter().flat_map(|p| &p.products) {
+            let mut product = Product { id: Uuid::new_v4(), name: name.clone() };
+            tx.put_by::<ProductNameIdx>(&mut product);
+            products.insert(product.id);
+
             for (_label, cs) in &prod.content_sets {
+                if let Some(other) = labels.insert(_label.clone(), name.clone()) {
+                    bail!("Content set {} is in both {} and {}", _label, other, name);
+                }
+                let mut content_set = ContentSet {
+                    id: Uuid::new_v4(),
+                    label: _label.clone(),
+                    name: cs.name.clone(),
+                    product_id: product.id,
+                };
+                tx.put_by::<ContentSetLabelIdx>(&mut content_set);
+                content_sets.insert(content_set.id);
+
                 let urls = cs.baseurl.iter().cloned();
                 let urls = urls.cartesian_product(cs.basearch.iter().map(Some).chain(None));
                 let urls = urls.cartesian_product(cs.releasever.iter().map(Some).chain(None));
@@ -275,44 +616,55 @@ impl Reposcan {
                         releasever: rv.map(ToString::to_string),
                         revision: None,
                         id: Uuid::new_v4(),
+                        content_set_id: content_set.id,
                     }
                 }).collect::<Vec<_>>();
 
                 println!("Adding {} repos for cs: {}", repos.len(), _label);
-                self.db.in_wtx(|tx| {
-                    for mut r in repos {
-                        if let Some(old) = tx.get_by::<RepoUrl>(&r.url) {
-                            r.revision = old.revision;
-                        }
-                        tx.put_by::<RepoUrl>(&mut r);
+                for mut repo in repos {
+                    if let Some(old) = tx.get_by::<RepoUrl>(&repo.url) {
+                        repo.revision = old.revision;
                     }
-                });
+                    tx.put_by::<RepoUrl>(&mut repo);
+                    repo_ids.insert(repo.id);
+                }
             }
         }
+
+        // Deletes cascade, removing a product removes its content sets, and those remove their repos
+        let products = tx.scan_keys::<Product>().filter(|id| !products.contains(id)).collect::<Vec<_>>();
+        let content_sets = tx.scan_keys::<ContentSet>().filter(|id| !content_sets.contains(id)).collect::<Vec<_>>();
+        let repos = tx.scan_keys::<Repo>().filter(|id| !repo_ids.contains(id)).collect::<Vec<_>>();
+        println!("Removing {} products, {} content sets and {} repos", products.len(), content_sets.len(), repos.len());
+
+        for id in &products {
+            tx.delete::<Product>(id);
+        }
+        for id in &content_sets {
+            tx.delete::<ContentSet>(id);
+        }
+        for id in &repos {
+            tx.delete::<Repo>(id);
+        }
+        tx.commit();
         Ok(())
     }
 
     pub fn sync(&mut self) -> Result<()> {
-        let repos = {
-            self.db.in_tx(|tx| {
-                println!("Package: {:?}", tx.scan::<Pkg>().count());
-                println!("Advs: {:?}", tx.scan::<Advisory>().count());
-                println!("Repos: {:?}", tx.scan::<Repo>().count());
-                println!("PKG-Advs: {:?}", tx.scan::<PkgAdvisory>().count());
-                println!("PKG-repos: {:?}", tx.scan::<PkgRepo>().count());
-                println!("adv-repos: {:?}", tx.scan::<AdvisoryRepo>().count());
-                println!("Modules: {:?}", tx.scan::<Module>().count());
-                println!("Streams: {:?}", tx.scan::<ModuleStream>().count());
-                tx.scan::<Repo>().collect::<Vec<_>>()
-            })
-        };
+        let stats = self.db.stats()?;
+        for (table, tree) in &stats.tables {
+            println!("{}: {}", table, tree.entries);
+        }
+        println!("Size: {} of {} bytes used", stats.used_size, stats.file_size);
+
+        let repos = self.db.in_tx(|tx| tx.scan::<Repo>().collect::<Vec<_>>());
 
         repos.into_iter().par_bridge().for_each(|r| {
             match self.sync_repo(&r) {
                 Ok(_) => {}
                 Err(e) => {
+                    // Failed attempt is recorded in the sync log, data of the previous revision is kept
                     println!("Could not sync repo :{} :{}", r.url, e);
-                    self.db.in_wtx(|w| w.delete::<Repo>(&r.id));
                 }
             }
         });
@@ -325,13 +677,40 @@ impl Reposcan {
         let mut scanner = RepoScanner {
             repo: repo.clone(),
             db: self.db.clone(),
+            sync: RepoSync {
+                // Allocated together with storing the attempt
+                id: RepoSyncId { repo_id: repo.id, seq: Id::default() },
+                url: repo.url.clone(),
+                started: Utc::now(),
+                finished: None,
+                old_revision: repo.revision.clone(),
+                new_revision: None,
+                primary_checksum: None,
+                pkgs_added: 0,
+                pkgs_removed: 0,
+                advs_added: 0,
+                advs_removed: 0,
+                error: None,
+            },
         };
-        if let Err(err) = syncer.sync_md(&mut scanner) {
+        let res = syncer.sync_md(&mut scanner);
+
+        let mut sync = scanner.sync;
+        sync.finished = Some(Utc::now());
+        if let Err(err) = &res {
+            sync.error = Some(format!("{:?}", err));
+        }
+        self.db.in_wtx(|tx| {
+            sync.id.seq = tx.next_id::<RepoSync>();
+            tx.put(&sync);
+        });
+
+        if let Err(err) = res {
             use rpmsync::ErrorImpl;
             match *err {
                 ErrorImpl::Ureq(rpmsync::ureq::Error::Status(code, _)) if code / 100 == 4 => {
-                    println!("{} not found, deleting repo", repo.url);
-                    self.db.clone().in_wtx(|tx| tx.delete::<Repo>(&repo.id));
+                    // Repo stays until it is dropped from the repolist, the failed attempt is in its sync history
+                    println!("{} not found, sync failed", repo.url);
                     return Ok(());
                 }
                 other => {
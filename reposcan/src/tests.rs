@@ -0,0 +1,400 @@
+//! Scanners are fed parsed metadata directly, the tests do not touch the network
+use super::*;
+use rpmsync::ModuleTarget;
+
+fn repo(tx: &mut Wtx, url: &str) -> Repo {
+    let product = Product { id: Uuid::new_v4(), name: format!("product {}", url) };
+    let cs = ContentSet { id: Uuid::new_v4(), label: format!("cs-{}", url), name: url.to_string(), product_id: product.id };
+    let repo = Repo {
+        id: Uuid::new_v4(),
+        label: format!("repo-{}", url),
+        url: url.to_string(),
+        basearch: None,
+        releasever: None,
+        revision: None,
+        content_set_id: cs.id,
+    };
+    tx.put(&product);
+    tx.put(&cs);
+    tx.put(&repo);
+    repo
+}
+
+fn modulemd(name: &str, stream: &str, dependencies: json::Value) -> Chunk {
+    json::from_value(json::json!({
+        "document": "modulemd",
+        "data": {
+            "name": name,
+            "stream": stream,
+            "version": 8040020210708u64,
+            "context": "522a0ee4",
+            "arch": "x86_64",
+            "license": {},
+            "dependencies": dependencies,
+            "artifacts": { "rpms": [format!("{}-1.0-1.module+el8.x86_64", name)] },
+        }
+    })).unwrap()
+}
+
+fn module_scanner(repo_id: Uuid, chunks: Vec<Chunk>) -> ModuleScanner {
+    let mut scanner = ModuleScanner { repo_id, modules: vec![], defaults: vec![] };
+    for chunk in chunks {
+        scanner.on_module_chunk(chunk);
+    }
+    scanner
+}
+
+fn deps_of(tx: &impl ROps, stream_id: Uuid) -> Vec<(String, bool, Vec<String>)> {
+    tx.referencing::<StreamDepModuleStreamRef>(&stream_id)
+        .into_iter()
+        .filter_map(|id| tx.get::<StreamDep>(&id))
+        .map(|dep| (dep.id.module, dep.id.build, dep.streams))
+        .collect()
+}
+
+#[test]
+fn test_module_dependencies() {
+    let db = cache::temporary();
+    let mut tx = db.wtx();
+    let repo = repo(&mut tx, "https://cdn/appstream");
+    let chunks = vec![
+        modulemd("nodejs", "14", json::json!([
+            { "requires": { "platform": ["el8"], "python": ["3.6"] }, "buildrequires": { "platform": ["el8.4.0"] } },
+            { "requires": { "platform": ["el8"], "python": ["3.8", "3.6"] } },
+            { "requires": { "platform": ["el8"], "perl": [] } },
+        ])),
+        json::from_value(json::json!({
+            "document": "modulemd-defaults",
+            "data": { "module": "nodejs", "stream": "14", "profiles": { "14": ["common"] } }
+        })).unwrap(),
+    ];
+    let mut diff = RepoDiff::load(&tx, repo.id);
+    module_scanner(repo.id, chunks).write(&mut tx, &mut diff);
+
+    let stream = tx.scan::<ModuleStream>().next().unwrap();
+    assert!(stream.default);
+    assert_eq!(default_of(&tx, repo.id, "nodejs").unwrap().profiles_of("14"), &["common".to_string()]);
+    let mut deps = deps_of(&tx, stream.id);
+    deps.sort();
+    // Alternatives are merged per module, an empty list in any of them accepts every stream
+    assert_eq!(deps, vec![
+        ("perl".to_string(), false, vec![]),
+        ("platform".to_string(), false, vec!["el8".to_string()]),
+        ("platform".to_string(), true, vec!["el8.4.0".to_string()]),
+        ("python".to_string(), false, vec!["3.6".to_string(), "3.8".to_string()]),
+    ]);
+    tx.commit();
+}
+
+fn repolist(value: json::Value) -> repolist::Repolist {
+    json::from_value(value).unwrap()
+}
+
+fn content_set(name: &str) -> json::Value {
+    json::json!({
+        "name": name,
+        "baseurl": "https://cdn/content/$releasever/$basearch/appstream",
+        "basearch": "x86_64",
+        "releasever": ["8", "9"],
+    })
+}
+
+#[test]
+fn test_load_repolist() {
+    let mut scanner = Reposcan { db: cache::temporary() };
+    scanner.load_repolist(repolist(json::json!([
+        { "products": { "RHEL": { "content_sets": { "rhel-appstream": content_set("AppStream") } } } }
+    ]))).unwrap();
+
+    let (cs, repos) = scanner.db.in_tx(|tx| {
+        let cs = tx.get_by::<ContentSetLabelIdx>(&"rhel-appstream".to_string()).unwrap();
+        (cs.clone(), repos_of(tx, &cs.id))
+    });
+    let mut urls = repos.iter().map(|r| r.url.as_str()).collect::<Vec<_>>();
+    urls.sort();
+    assert_eq!(urls, vec!["https://cdn/content/8/x86_64/appstream", "https://cdn/content/9/x86_64/appstream"]);
+
+    // Label listed under two products is rejected, and nothing of the new repolist is stored
+    let err = scanner.load_repolist(repolist(json::json!([
+        { "products": { "RHEL": { "content_sets": { "rhel-appstream": content_set("AppStream") } } } },
+        { "products": { "Extras": { "content_sets": { "rhel-appstream": content_set("Extras") } } } },
+    ])));
+    assert!(err.is_err());
+    scanner.db.in_tx(|tx| {
+        assert_eq!(tx.count::<Product>(), 1);
+        assert!(tx.get_by::<ProductNameIdx>(&"Extras".to_string()).is_none());
+    });
+
+    // Content set moved to another product keeps its repos, the old product is removed
+    scanner.load_repolist(repolist(json::json!([
+        { "products": { "RHEL 8": { "content_sets": { "rhel-appstream": content_set("AppStream") } } } }
+    ]))).unwrap();
+    scanner.db.in_tx(|tx| {
+        let product = tx.get_by::<ProductNameIdx>(&"RHEL 8".to_string()).unwrap();
+        assert_eq!(tx.count::<Product>(), 1);
+        assert_eq!(content_sets_of(tx, &product.id).iter().map(|cs| cs.id).collect::<Vec<_>>(), vec![cs.id]);
+        let mut ids = repos_of(tx, &cs.id).iter().map(|r| r.id).collect::<Vec<_>>();
+        let mut old = repos.iter().map(|r| r.id).collect::<Vec<_>>();
+        ids.sort();
+        old.sort();
+        assert_eq!(ids, old);
+    });
+
+    scanner.load_repolist(vec![]).unwrap();
+    scanner.db.in_tx(|tx| assert_eq!((tx.count::<ContentSet>(), tx.count::<Repo>()), (0, 0)));
+}
+
+/// Local server answering every request with given status, returns its url
+fn serve_status(status: &'static str) -> String {
+    use std::io::{Read, Write};
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut buf = [0; 4096];
+            let _ = stream.read(&mut buf);
+            let _ = write!(stream, "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+        }
+    });
+    url
+}
+
+#[test]
+fn test_sync_not_found() {
+    let scanner = Reposcan { db: cache::temporary() };
+    let mut repo = scanner.db.in_wtx(|tx| repo(tx, &serve_status("404 Not Found")));
+    repo.revision = Some("1620000000".to_string());
+    scanner.db.in_wtx(|tx| tx.put(&repo));
+
+    scanner.sync_repo(&repo).unwrap();
+    scanner.sync_repo(&repo).unwrap();
+
+    // Repo and its data are kept, both failed attempts are recorded
+    scanner.db.in_tx(|tx| {
+        let stored = tx.get::<Repo>(&repo.id).unwrap();
+        assert_eq!(stored.revision, repo.revision);
+        let syncs = syncs_of(tx, &repo.id);
+        assert_eq!(syncs.iter().map(|s| s.id.seq).collect::<Vec<_>>(), vec![Id(1), Id(2)]);
+        assert!(syncs.iter().all(|s| !s.succeeded() && s.error.as_ref().unwrap().contains("404")));
+        let last = last_sync(tx, &repo.id).unwrap();
+        assert_eq!((last.id.seq, last.old_revision), (Id(2), repo.revision.clone()));
+    });
+}
+
+fn detail(pkg: &Pkg) -> PkgDetail {
+    PkgDetail {
+        pkg_id: pkg.id,
+        summary: format!("{} summary", pkg.nevra.name),
+        description: String::new(),
+        checksum: None,
+        packager: None,
+        url: None,
+        build_time: 0,
+        file_time: 0,
+        package_size: 0,
+        installed_size: 0,
+        archive_size: 0,
+        source_rpm: Some(format!("{}-{}-{}.src.rpm", pkg.nevra.name, pkg.nevra.ver, pkg.nevra.rel)),
+    }
+}
+
+fn new_pkg(nevra: &str) -> Pkg {
+    Pkg { id: Uuid::new_v4(), nevra: Nevra::parse(nevra).unwrap() }
+}
+
+fn update(name: &str, pkgs: &[&str], cves: &[&str], bugs: &[&str]) -> ParsedUpdate {
+    ParsedUpdate {
+        adv: Advisory {
+            id: Uuid::new_v4(),
+            r#type: "security".to_string(),
+            name: name.to_string(),
+            title: name.to_string(),
+            severity: Severity::Important,
+            status: "final".to_string(),
+            from: String::new(),
+            summary: None,
+            desc: None,
+            solution: None,
+            rights: None,
+            release: None,
+            issued: None,
+            updated: None,
+        },
+        pkgs: pkgs.iter().map(|n| (new_pkg(n), None)).collect(),
+        cves: cves.iter().map(|c| Cve { name: c.to_string(), href: None }).collect(),
+        bugs: bugs.iter().map(|b| Bugzilla { id: b.to_string(), href: None, title: None }).collect(),
+    }
+}
+
+fn module_update(name: &str, pkg: &str, module: &str, stream: &str) -> ParsedUpdate {
+    let module = rpmrepo::updateinfo::Module {
+        name: module.to_string(),
+        stream: stream.to_string(),
+        arch: "x86_64".to_string(),
+        version: 8040020210708,
+        context: "522a0ee4".to_string(),
+    };
+    ParsedUpdate { pkgs: vec![(new_pkg(pkg), Some(module))], ..update(name, &[], &[], &[]) }
+}
+
+/// Write one revision of the repo, as `RepoScanner` does once everything is fetched
+fn write_revision(db: &Database, repo_id: Uuid, pkgs: &[&str], modules: Vec<Chunk>, advs: Vec<ParsedUpdate>) -> RepoSync {
+    let mut sync = RepoSync {
+        id: RepoSyncId { repo_id, seq: Id::default() },
+        url: String::new(),
+        started: Utc::now(),
+        finished: None,
+        old_revision: None,
+        new_revision: None,
+        primary_checksum: None,
+        pkgs_added: 0,
+        pkgs_removed: 0,
+        advs_added: 0,
+        advs_removed: 0,
+        error: None,
+    };
+    let packages = pkgs.iter().map(|n| {
+        let pkg = new_pkg(n);
+        let detail = detail(&pkg);
+        (pkg, detail, format!("Packages/{}.rpm", n))
+    }).collect();
+    let mut tx = db.wtx();
+    let mut diff = RepoDiff::load(&tx, repo_id);
+    PackageScanner { repo_id, packages }.write(&mut tx, &mut diff, &mut sync);
+    module_scanner(repo_id, modules).write(&mut tx, &mut diff);
+    UpdateScanner { repo_id, advs }.write(&mut tx, &mut diff, &mut sync);
+    diff.remove_stale(&mut tx, repo_id, &mut sync);
+    tx.commit();
+    sync
+}
+
+#[test]
+fn test_updateinfo_packages() {
+    let db = cache::temporary();
+    let repo = db.in_wtx(|tx| repo(tx, "https://cdn/baseos"));
+    let bash = "bash-5.1.8-6.el9.x86_64";
+    let openssl = "openssl-1:3.0.7-1.el9.x86_64";
+
+    // Package only listed in updateinfo is created without details, and counted as added
+    let sync = write_revision(&db, repo.id, &[bash], vec![], vec![update("RHSA-2021:1024", &[openssl], &[], &[])]);
+    assert_eq!((sync.pkgs_added, sync.advs_added), (2, 1));
+    let id = db.in_tx(|tx| tx.get_by::<PkgNevraIdx>(&Nevra::parse(openssl).unwrap()).unwrap().id);
+    db.in_tx(|tx| assert!(tx.get::<PkgDetail>(&id).is_none()));
+
+    // Once primary lists it, details and source are stored under the same id
+    let sync = write_revision(&db, repo.id, &[bash, openssl], vec![], vec![update("RHSA-2021:1024", &[openssl], &[], &[])]);
+    assert_eq!((sync.pkgs_added, sync.pkgs_removed, sync.advs_added), (0, 0, 0));
+    db.in_tx(|tx| {
+        assert_eq!(tx.get::<PkgDetail>(&id).unwrap().summary, "openssl summary");
+        assert_eq!(source_of(tx, &id).unwrap().filename, "openssl-3.0.7-1.el9.src.rpm");
+        assert!(tx.get::<PkgLocation>(&PkgRepoId { pkg_id: id, repo_id: repo.id }).is_some());
+    });
+}
+
+#[test]
+fn test_advisory_links() {
+    let db = cache::temporary();
+    let repo = db.in_wtx(|tx| repo(tx, "https://cdn/baseos"));
+    let bash = "bash-5.1.8-6.el9.x86_64";
+    let openssl = "openssl-1:3.0.7-1.el9.x86_64";
+    let name = "RHSA-2021:1024";
+    let pkgs_of = |db: &Database| db.in_tx(|tx| {
+        let adv = tx.get_by::<AdvisoryNameIdx>(&name.to_string()).unwrap();
+        let mut pkgs = tx.referencing::<PkgAdvisoryAdvisoryRef>(&adv.id)
+            .into_iter()
+            .filter_map(|id| tx.get::<Pkg>(&id.pkg_id))
+            .map(|p| p.nevra.name)
+            .collect::<Vec<_>>();
+        pkgs.sort();
+        pkgs
+    });
+    let refs_of = |db: &Database| db.in_tx(|tx| {
+        let cves = cves_fixed_by(tx, name).into_iter().map(|c| c.name).collect::<Vec<_>>();
+        let bugs = bugs_fixed_by(tx, name).into_iter().map(|b| b.id).collect::<Vec<_>>();
+        (cves, bugs)
+    });
+
+    write_revision(&db, repo.id, &[bash, openssl], vec![], vec![update(name, &[bash, openssl], &["CVE-2021-3449"], &["1941554"])]);
+    assert_eq!(pkgs_of(&db), vec!["bash", "openssl"]);
+    assert_eq!(refs_of(&db), (vec!["CVE-2021-3449".to_string()], vec!["1941554".to_string()]));
+
+    // Links missing from the new revision are deleted, along with CVEs and bugs no advisory refers to
+    write_revision(&db, repo.id, &[bash, openssl], vec![], vec![update(name, &[openssl], &[], &[])]);
+    assert_eq!(pkgs_of(&db), vec!["openssl"]);
+    assert_eq!(refs_of(&db), (vec![], vec![]));
+    db.in_tx(|tx| {
+        assert!(tx.get::<Cve>(&"CVE-2021-3449".to_string()).is_none());
+        assert!(tx.get::<Bugzilla>(&"1941554".to_string()).is_none());
+    });
+
+    write_revision(&db, repo.id, &[bash, openssl], vec![], vec![update(name, &[bash, openssl], &["CVE-2021-3449"], &["1941554"])]);
+    assert_eq!(pkgs_of(&db), vec!["bash", "openssl"]);
+    assert_eq!(refs_of(&db), (vec!["CVE-2021-3449".to_string()], vec!["1941554".to_string()]));
+}
+
+#[test]
+fn test_stale_streams() {
+    let db = cache::temporary();
+    let repo = db.in_wtx(|tx| repo(tx, "https://cdn/appstream"));
+    let streams_of_repo = |db: &Database| db.in_tx(|tx| {
+        let mut streams = tx.scan::<ModuleStream>()
+            .map(|s| (tx.get::<Module>(&s.attrs.module_id).unwrap().attrs.name, s.attrs.name))
+            .collect::<Vec<_>>();
+        streams.sort();
+        streams
+    });
+
+    write_revision(&db, repo.id, &[], vec![
+        modulemd("nodejs", "14", json::json!([{ "requires": { "platform": ["el8"] } }])),
+        modulemd("nodejs", "16", json::json!([])),
+        modulemd("perl", "5.30", json::json!([])),
+    ], vec![]);
+    assert_eq!(streams_of_repo(&db), vec![
+        ("nodejs".to_string(), "14".to_string()),
+        ("nodejs".to_string(), "16".to_string()),
+        ("perl".to_string(), "5.30".to_string()),
+    ]);
+    let node14 = db.in_tx(|tx| tx.scan::<ModuleStream>().find(|s| s.attrs.name == "14").unwrap());
+
+    // Stream kept by the new revision keeps its id, its rows are replaced
+    write_revision(&db, repo.id, &[], vec![modulemd("nodejs", "14", json::json!([]))], vec![]);
+    assert_eq!(streams_of_repo(&db), vec![("nodejs".to_string(), "14".to_string())]);
+    db.in_tx(|tx| {
+        assert_eq!(tx.get::<ModuleStream>(&node14.id).map(|s| s.id), Some(node14.id));
+        assert!(deps_of(tx, node14.id).is_empty());
+        assert_eq!(packages_of(tx, &node14.id).len(), 1);
+        assert_eq!(tx.count::<Module>(), 1);
+        assert_eq!(tx.count::<StreamPkg>(), 1);
+        assert_eq!(tx.count::<StreamDep>(), 0);
+    });
+
+    write_revision(&db, repo.id, &[], vec![], vec![]);
+    db.in_tx(|tx| assert_eq!((tx.count::<Module>(), tx.count::<ModuleStream>(), tx.count::<StreamPkg>()), (0, 0, 0)));
+}
+
+#[test]
+fn test_stale_advisory_stream() {
+    let db = cache::temporary();
+    let (appstream, other) = db.in_wtx(|tx| (repo(tx, "https://cdn/appstream"), repo(tx, "https://cdn/other")));
+    let nodejs = "nodejs-1.0-1.module+el8.x86_64";
+    let name = "RHSA-2021:3280";
+    let streams_of = |db: &Database| db.in_tx(|tx| {
+        let adv = tx.get_by::<AdvisoryNameIdx>(&name.to_string()).unwrap();
+        tx.referencing::<PkgAdvisoryAdvisoryRef>(&adv.id).into_iter().map(|id| id.stream_id).collect::<Vec<_>>()
+    });
+
+    write_revision(&db, other.id, &[nodejs], vec![], vec![update(name, &[nodejs], &[], &[])]);
+    write_revision(&db, appstream.id, &[nodejs], vec![modulemd("nodejs", "14", json::json!([]))], vec![
+        module_update(name, nodejs, "nodejs", "14"),
+    ]);
+    let stream = db.in_tx(|tx| tx.scan::<ModuleStream>().next().unwrap());
+    let mut streams = streams_of(&db);
+    streams.sort();
+    assert_eq!(streams, vec![None, Some(stream.id)]);
+
+    // Advisory stays with the other repo, its link to the dropped stream goes away with the stream
+    write_revision(&db, appstream.id, &[nodejs], vec![], vec![]);
+    db.in_tx(|tx| assert_eq!(tx.count::<ModuleStream>(), 0));
+    assert_eq!(streams_of(&db), vec![None]);
+}
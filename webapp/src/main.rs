@@ -1,20 +1,14 @@
-use bindb::{Database, ROps};
+use bindb::Table;
 use cache::*;
 
 fn main() {
-    let db = Database::open("data.mdbx")
-        .register::<Repo>()
-        .register::<Pkg>()
-        .register::<Advisory>()
-        .register::<Module>()
-        .register::<ModuleStream>()
-        .register::<PkgAdvisory>()
-        .register::<PkgRepo>()
-        .register::<AdvisoryRepo>();
+    let db = cache::open("data.mdbx");
 
-    db.in_tx(|tx| {
-        println!("We have {:?} packages", tx.scan::<Pkg>().count());
-    });
+    let stats = db.stats().unwrap();
+    println!("We have {} packages", stats.tables[Pkg::NAME].entries);
+
+    let changes = db.changes();
+    println!("Data last modified at {} (change {})", changes.modified, changes.seq);
 
     println!("Hello, world!");
 }